@@ -0,0 +1,106 @@
+#!/usr/bin/env bash
+# posts two jobs with a bounty against a local ICRC ledger, hires through one and cancels
+# the other, then checks that the hire got paid and the company got its refund.
+# needs a running local replica: dfx start --background
+# and IC_VERSION set to an IC revision that publishes the ICRC-1 ledger wasm.
+set -euo pipefail
+
+CANISTER=assignment_backend
+: "${IC_VERSION:?set IC_VERSION to an IC revision that publishes ic-icrc1-ledger.wasm.gz}"
+LEDGER_DID_PATH=${LEDGER_DID_PATH:-rs/ledger_suite/icrc1/ledger/ledger.did}
+
+FEE=10000
+BOUNTY=1000000
+INITIAL_BALANCE=100000000
+
+for identity in escrow-minter escrow-applicant; do
+    dfx identity new --storage-mode plaintext "$identity" >/dev/null 2>&1 || true
+done
+
+COMPANY=$(dfx identity get-principal)
+MINTER=$(dfx identity get-principal --identity escrow-minter)
+APPLICANT=$(dfx identity get-principal --identity escrow-applicant)
+
+# the ledger lives in a project of its own, so dfx.json stays deployable without it
+LEDGER_DIR=$(mktemp -d)
+trap 'rm -rf "$LEDGER_DIR"' EXIT
+
+cat >"$LEDGER_DIR/dfx.json" <<EOF
+{
+  "canisters": {
+    "icrc1_ledger": {
+      "type": "custom",
+      "candid": "https://raw.githubusercontent.com/dfinity/ic/$IC_VERSION/$LEDGER_DID_PATH",
+      "wasm": "https://download.dfinity.systems/ic/$IC_VERSION/canisters/ic-icrc1-ledger.wasm.gz"
+    }
+  },
+  "version": 1
+}
+EOF
+
+ledger() {
+    (cd "$LEDGER_DIR" && dfx canister call icrc1_ledger "$@")
+}
+
+balance_of() {
+    ledger icrc1_balance_of "(record { owner = principal \"$1\" })" | tr -d '_' | grep -o '[0-9]\+' | head -1
+}
+
+(cd "$LEDGER_DIR" && dfx deploy icrc1_ledger --yes --argument "(variant { Init = record {
+    token_symbol = \"TEST\";
+    token_name = \"Test\";
+    minting_account = record { owner = principal \"$MINTER\" };
+    transfer_fee = $FEE;
+    metadata = vec {};
+    initial_balances = vec { record { record { owner = principal \"$COMPANY\" }; $INITIAL_BALANCE } };
+    archive_options = record {
+        num_blocks_to_archive = 1000;
+        trigger_threshold = 2000;
+        controller_id = principal \"$COMPANY\";
+    };
+    feature_flags = opt record { icrc2 = true };
+} })")
+LEDGER=$(cd "$LEDGER_DIR" && dfx canister id icrc1_ledger)
+
+dfx deploy "$CANISTER" --mode reinstall --yes --argument "(opt record {
+    admins = vec {};
+    ledger = opt record { canister_id = principal \"$LEDGER\"; token_symbol = \"TEST\"; fee = $FEE };
+})"
+BACKEND=$(dfx canister id "$CANISTER")
+
+dfx canister call "$CANISTER" create_company_profile \
+    '(record { name = "acme"; logo = ""; twitter = ""; website = "" })'
+
+# every transfer_from charges the fee on top of the bounty
+ledger icrc2_approve "(record {
+    spender = record { owner = principal \"$BACKEND\" };
+    amount = $((2 * (BOUNTY + FEE)));
+})"
+dfx canister call "$CANISTER" create_job \
+    "(record { position = \"hire\"; description = \"\"; bounty = $BOUNTY }, vec {})"
+dfx canister call "$CANISTER" create_job \
+    "(record { position = \"cancel\"; description = \"\"; bounty = $BOUNTY }, vec {})"
+
+dfx canister call --identity escrow-applicant "$CANISTER" create_applicant_profile \
+    '(record { first_name = "ada"; last_name = "lovelace"; nickname = ""; bio = "" }, vec {})'
+dfx canister call --identity escrow-applicant "$CANISTER" apply_to_job \
+    '(record { job_id = 1; contact_email = "ada@example.com"; salary_from = 0; salary_to = 0 })'
+
+dfx canister call "$CANISTER" make_offer '(1 : nat64, 1 : nat64, true)'
+dfx canister call --identity escrow-applicant "$CANISTER" accept_offer '(1 : nat64, true)'
+dfx canister call "$CANISTER" cancel_job '(2 : nat64)'
+
+# payouts run after the transition replied, give them a moment
+for job_id in 1 2; do
+    for _ in $(seq 10); do
+        dfx canister call "$CANISTER" get_escrow "($job_id : nat64)" | grep -q 'Paid' && break
+        sleep 1
+    done
+    dfx canister call "$CANISTER" get_escrow "($job_id : nat64)" | grep -q 'Paid'
+done
+
+# the escrow pays the fee of the transfer out
+test "$(balance_of "$APPLICANT")" -eq $((BOUNTY - FEE))
+test "$(balance_of "$COMPANY")" -eq $((INITIAL_BALANCE - FEE - 2 * (BOUNTY + FEE) + BOUNTY - FEE))
+
+echo "escrow test passed"
@@ -0,0 +1,24 @@
+#!/usr/bin/env bash
+# checks that the backend keeps its data and id counters across an upgrade.
+# needs a running local replica: dfx start --background
+set -euo pipefail
+
+CANISTER=assignment_backend
+
+dfx deploy "$CANISTER" --mode reinstall --yes
+
+dfx canister call "$CANISTER" create_company_profile \
+    '(record { name = "acme"; logo = ""; twitter = ""; website = "" })'
+dfx canister call "$CANISTER" create_job \
+    '(record { position = "before upgrade"; description = ""; bounty = 0 }, vec {})'
+
+dfx deploy "$CANISTER" --upgrade-unchanged
+
+dfx canister call "$CANISTER" create_job \
+    '(record { position = "after upgrade"; description = ""; bounty = 0 }, vec {})'
+
+# job created before the upgrade is still there, and the counter kept going
+dfx canister call "$CANISTER" get_job '(1 : nat64)' | grep -q '"before upgrade"'
+dfx canister call "$CANISTER" get_job '(2 : nat64)' | grep -q '"after upgrade"'
+
+echo "upgrade test passed"
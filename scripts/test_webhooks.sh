@@ -0,0 +1,72 @@
+#!/usr/bin/env bash
+# registers a webhook pointing at a stub server on this machine, runs an application
+# through an offer and checks that the stub received every event with a valid signature.
+# needs a running local replica: dfx start --background
+set -euo pipefail
+
+CANISTER=assignment_backend
+PORT=${WEBHOOK_PORT:-8787}
+SECRET=local-webhook-secret
+
+dfx identity new --storage-mode plaintext webhook-applicant >/dev/null 2>&1 || true
+
+STUB_DIR=$(mktemp -d)
+RECEIVED="$STUB_DIR/received"
+touch "$RECEIVED"
+
+# writes the event of every correctly signed payload to $RECEIVED, one per line
+cat >"$STUB_DIR/stub.py" <<'EOF'
+import hashlib, hmac, json, sys
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+port, secret, received = int(sys.argv[1]), sys.argv[2].encode(), sys.argv[3]
+
+class Stub(BaseHTTPRequestHandler):
+    def do_POST(self):
+        body = self.rfile.read(int(self.headers["Content-Length"]))
+        expected = "sha256=" + hmac.new(secret, body, hashlib.sha256).hexdigest()
+        signed = hmac.compare_digest(expected, self.headers.get("X-Webhook-Signature", ""))
+
+        if signed:
+            with open(received, "a") as out:
+                out.write(json.loads(body)["event"] + "\n")
+
+        self.send_response(200 if signed else 401)
+        self.end_headers()
+
+HTTPServer(("127.0.0.1", port), Stub).serve_forever()
+EOF
+
+python3 "$STUB_DIR/stub.py" "$PORT" "$SECRET" "$RECEIVED" &
+STUB_PID=$!
+trap 'kill "$STUB_PID"; rm -rf "$STUB_DIR"' EXIT
+
+dfx deploy "$CANISTER" --mode reinstall --yes
+
+dfx canister call "$CANISTER" create_company_profile \
+    '(record { name = "acme"; logo = ""; twitter = ""; website = "" })'
+dfx canister call "$CANISTER" register_webhook \
+    "(record { url = \"http://127.0.0.1:$PORT/hooks\"; secret = \"$SECRET\" })"
+dfx canister call "$CANISTER" create_job \
+    '(record { position = "hire"; description = ""; bounty = 0 }, vec {})'
+
+dfx canister call --identity webhook-applicant "$CANISTER" create_applicant_profile \
+    '(record { first_name = "ada"; last_name = "lovelace"; nickname = ""; bio = "" }, vec {})'
+dfx canister call --identity webhook-applicant "$CANISTER" apply_to_job \
+    '(record { job_id = 1; contact_email = "ada@example.com"; salary_from = 0; salary_to = 0 })'
+
+dfx canister call "$CANISTER" make_offer '(1 : nat64, 1 : nat64, true)'
+dfx canister call --identity webhook-applicant "$CANISTER" accept_offer '(1 : nat64, true)'
+
+# deliveries go out from a timer after the calls replied, give them a moment
+for _ in $(seq 30); do
+    [ "$(wc -l <"$RECEIVED")" -ge 3 ] && break
+    sleep 1
+done
+
+test "$(sort "$RECEIVED" | tr '\n' ' ')" = "application_received offer_accepted offer_made "
+
+# nothing is left waiting for a retry
+dfx canister call "$CANISTER" get_webhook_deliveries '(record {})' | grep -q 'total = 0'
+
+echo "webhook test passed"
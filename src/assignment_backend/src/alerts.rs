@@ -0,0 +1,533 @@
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::time;
+use ic_cdk_timers::TimerId;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+use std::time::Duration;
+
+use crate::inbox::{self, NotificationKind};
+use crate::{search, skills};
+use crate::{BoardError, BoardResult, Job, JobStatus, JOB_STORE};
+
+pub const MAX_SAVED_SEARCHES: usize = 10;
+pub const MAX_SEARCH_NAME_LENGTH: usize = 50;
+// saved searches checked against new jobs per call or timer tick
+const BATCH_SIZE: usize = 100;
+
+// a job matches when it asks for one of the skills (or one below it),
+// its bounty is at least `min_bounty` and its position or description has every keyword
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct SavedSearch {
+    pub id: u64,
+    pub owner: Principal,
+    pub name: String,
+    pub skill_ids: Vec<u16>,
+    pub min_bounty: Option<u64>,
+    pub keywords: Vec<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct SavedSearchParams {
+    pub name: String,
+    pub skill_ids: Vec<u16>,
+    pub min_bounty: Option<u64>,
+    // split into words the same way job search does
+    pub keywords: String,
+}
+
+pub type SavedSearchStore = BTreeMap<u64, SavedSearch>;
+// job id -> the last candidate saved search checked against it
+pub type PendingAlertStore = BTreeMap<u64, u64>;
+
+thread_local! {
+    pub(crate) static SAVED_SEARCH_ID_STORE: Cell<u64> = const { Cell::new(0) };
+    pub(crate) static SAVED_SEARCH_STORE: RefCell<SavedSearchStore> = RefCell::default();
+    // jobs whose alerts aren't all sent yet
+    pub(crate) static PENDING_ALERT_STORE: RefCell<PendingAlertStore> = RefCell::default();
+    // timers don't survive an upgrade, post_upgrade arms it again
+    static TIMER: Cell<Option<TimerId>> = const { Cell::new(None) };
+
+    // derived from SAVED_SEARCH_STORE. a new job only looks at the searches
+    // indexed under its skills and words, so the cost of posting a job doesn't
+    // grow with the number of saved searches.
+    static SEARCH_BY_OWNER_STORE: RefCell<BTreeMap<Principal, BTreeSet<u64>>> = RefCell::default();
+    static SEARCH_BY_SKILL_STORE: RefCell<BTreeMap<u16, BTreeSet<u64>>> = RefCell::default();
+    static SEARCH_BY_KEYWORD_STORE: RefCell<BTreeMap<String, BTreeSet<u64>>> = RefCell::default();
+}
+
+fn index(saved: &SavedSearch) {
+    SEARCH_BY_OWNER_STORE.with(|store| {
+        store
+            .borrow_mut()
+            .entry(saved.owner)
+            .or_default()
+            .insert(saved.id);
+    });
+    SEARCH_BY_SKILL_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+        for skill_id in &saved.skill_ids {
+            store.entry(*skill_id).or_default().insert(saved.id);
+        }
+    });
+    SEARCH_BY_KEYWORD_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+        for keyword in &saved.keywords {
+            store.entry(keyword.clone()).or_default().insert(saved.id);
+        }
+    });
+}
+
+fn unindex<K: Ord>(store: &mut BTreeMap<K, BTreeSet<u64>>, key: &K, id: u64) {
+    if let Some(ids) = store.get_mut(key) {
+        ids.remove(&id);
+
+        if ids.is_empty() {
+            store.remove(key);
+        }
+    }
+}
+
+fn remove_from_indexes(saved: &SavedSearch) {
+    SEARCH_BY_OWNER_STORE.with(|store| unindex(&mut store.borrow_mut(), &saved.owner, saved.id));
+    SEARCH_BY_SKILL_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+        for skill_id in &saved.skill_ids {
+            unindex(&mut store, skill_id, saved.id);
+        }
+    });
+    SEARCH_BY_KEYWORD_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+        for keyword in &saved.keywords {
+            unindex(&mut store, keyword, saved.id);
+        }
+    });
+}
+
+pub fn rebuild() {
+    SEARCH_BY_OWNER_STORE.with(|store| store.borrow_mut().clear());
+    SEARCH_BY_SKILL_STORE.with(|store| store.borrow_mut().clear());
+    SEARCH_BY_KEYWORD_STORE.with(|store| store.borrow_mut().clear());
+
+    SAVED_SEARCH_STORE.with(|store| store.borrow().values().for_each(index));
+}
+
+pub fn get(id: u64) -> Option<SavedSearch> {
+    SAVED_SEARCH_STORE.with(|store| store.borrow().get(&id).cloned())
+}
+
+pub fn of_owner(owner: &Principal) -> Vec<SavedSearch> {
+    let ids = SEARCH_BY_OWNER_STORE
+        .with(|store| store.borrow().get(owner).cloned())
+        .unwrap_or_default();
+
+    ids.into_iter().filter_map(get).collect()
+}
+
+fn get_owned(owner: &Principal, id: u64) -> BoardResult<SavedSearch> {
+    get(id)
+        .filter(|saved| saved.owner == *owner)
+        .ok_or_else(|| BoardError::not_found("saved search", id))
+}
+
+// checks the params and returns the name, skill ids and keywords to store
+fn validate(params: SavedSearchParams) -> BoardResult<(String, Vec<u16>, Vec<String>)> {
+    let name = params.name.trim().to_string();
+
+    if name.is_empty() || name.chars().count() > MAX_SEARCH_NAME_LENGTH {
+        return Err(BoardError::validation(
+            "name",
+            &format!("must be between 1 and {MAX_SEARCH_NAME_LENGTH} characters"),
+        ));
+    }
+
+    let skill_ids: BTreeSet<u16> = params.skill_ids.into_iter().collect();
+
+    if skill_ids.len() > skills::MAX_SKILLS_PER_REQUEST {
+        return Err(BoardError::validation("skill_ids", "too many skills"));
+    }
+
+    if let Some(skill_id) = skill_ids.iter().find(|id| skills::get(**id).is_none()) {
+        return Err(BoardError::not_found("skill", skill_id));
+    }
+
+    let keywords: BTreeSet<String> = search::tokenize(&params.keywords).into_iter().collect();
+
+    if keywords.len() > search::MAX_QUERY_TERMS {
+        return Err(BoardError::validation("keywords", "has too many words"));
+    }
+
+    // a search without either would have to be checked against every new job
+    if skill_ids.is_empty() && keywords.is_empty() {
+        return Err(BoardError::validation(
+            "skill_ids",
+            "a saved search needs skills or keywords",
+        ));
+    }
+
+    Ok((
+        name,
+        skill_ids.into_iter().collect(),
+        keywords.into_iter().collect(),
+    ))
+}
+
+pub fn create(owner: Principal, params: SavedSearchParams) -> BoardResult<SavedSearch> {
+    if of_owner(&owner).len() >= MAX_SAVED_SEARCHES {
+        return Err(BoardError::conflict(&format!(
+            "Saved search limit of {MAX_SAVED_SEARCHES} reached"
+        )));
+    }
+
+    let min_bounty = params.min_bounty;
+    let (name, skill_ids, keywords) = validate(params)?;
+
+    let id = SAVED_SEARCH_ID_STORE.with(|id| {
+        id.set(id.get() + 1);
+        id.get()
+    });
+
+    let saved = SavedSearch {
+        id,
+        owner,
+        name,
+        skill_ids,
+        min_bounty,
+        keywords,
+        created_at: time(),
+        updated_at: time(),
+    };
+
+    SAVED_SEARCH_STORE.with(|store| store.borrow_mut().insert(id, saved.clone()));
+    index(&saved);
+
+    Ok(saved)
+}
+
+pub fn update(owner: Principal, id: u64, params: SavedSearchParams) -> BoardResult<SavedSearch> {
+    let previous = get_owned(&owner, id)?;
+
+    let min_bounty = params.min_bounty;
+    let (name, skill_ids, keywords) = validate(params)?;
+
+    let saved = SavedSearch {
+        name,
+        skill_ids,
+        min_bounty,
+        keywords,
+        updated_at: time(),
+        ..previous.clone()
+    };
+
+    remove_from_indexes(&previous);
+    SAVED_SEARCH_STORE.with(|store| store.borrow_mut().insert(id, saved.clone()));
+    index(&saved);
+
+    Ok(saved)
+}
+
+pub fn delete(owner: Principal, id: u64) -> BoardResult<()> {
+    let saved = get_owned(&owner, id)?;
+
+    remove_from_indexes(&saved);
+    SAVED_SEARCH_STORE.with(|store| store.borrow_mut().remove(&id));
+
+    Ok(())
+}
+
+// called by a skill merge, saved searches on `from` look for `into` instead
+pub fn retarget(from: u16, into: u16) {
+    let ids = SEARCH_BY_SKILL_STORE
+        .with(|store| store.borrow().get(&from).cloned())
+        .unwrap_or_default();
+
+    for saved in ids.into_iter().filter_map(get) {
+        let mut skill_ids: BTreeSet<u16> = saved.skill_ids.iter().cloned().collect();
+        skill_ids.remove(&from);
+        skill_ids.insert(into);
+
+        let retargeted = SavedSearch {
+            skill_ids: skill_ids.into_iter().collect(),
+            ..saved.clone()
+        };
+
+        remove_from_indexes(&saved);
+        SAVED_SEARCH_STORE.with(|store| store.borrow_mut().insert(saved.id, retargeted.clone()));
+        index(&retargeted);
+    }
+}
+
+pub fn skills_in_use() -> BTreeSet<u16> {
+    SEARCH_BY_SKILL_STORE.with(|store| store.borrow().keys().cloned().collect())
+}
+
+fn matches(saved: &SavedSearch, job: &Job, words: &BTreeSet<String>) -> bool {
+    let skills_match = saved.skill_ids.is_empty()
+        || saved.skill_ids.iter().any(|wanted| {
+            job.required_skills
+                .keys()
+                .any(|skill_id| skills::covers(*wanted, *skill_id))
+        });
+
+    skills_match
+        && saved
+            .min_bounty
+            .is_none_or(|min_bounty| job.bounty >= min_bounty)
+        && saved.keywords.iter().all(|keyword| words.contains(keyword))
+}
+
+// the saved searches that can match the job, and the words of its text
+fn candidates(job: &Job) -> (BTreeSet<u64>, BTreeSet<String>) {
+    let words: BTreeSet<String> = search::tokenize(&job.position)
+        .into_iter()
+        .chain(search::tokenize(&job.description))
+        .collect();
+
+    // a saved skill matches the job's skills and everything above them
+    let skill_ids: BTreeSet<u16> = job
+        .required_skills
+        .keys()
+        .flat_map(|skill_id| skills::ancestors(*skill_id).into_iter().chain([*skill_id]))
+        .collect();
+
+    let mut candidates: BTreeSet<u64> = SEARCH_BY_SKILL_STORE.with(|store| {
+        let store = store.borrow();
+        skill_ids
+            .iter()
+            .filter_map(|skill_id| store.get(skill_id))
+            .flatten()
+            .cloned()
+            .collect()
+    });
+
+    SEARCH_BY_KEYWORD_STORE.with(|store| {
+        let store = store.borrow();
+        candidates.extend(words.iter().filter_map(|word| store.get(word)).flatten());
+    });
+
+    (candidates, words)
+}
+
+// checks the candidates after `cursor` against the job, `budget` of them at most.
+// returns the last one checked while some are left, None once they are all done.
+fn advance(job: &Job, cursor: u64, budget: &mut usize) -> Option<u64> {
+    let (candidates, words) = candidates(job);
+
+    let mut last = cursor;
+    for id in candidates.range((Bound::Excluded(cursor), Bound::Unbounded)) {
+        if *budget == 0 {
+            return Some(last);
+        }
+        *budget -= 1;
+        last = *id;
+
+        let Some(saved) = get(*id) else {
+            continue;
+        };
+
+        if matches(&saved, job, &words) {
+            inbox::push(
+                saved.owner,
+                NotificationKind::JobAlert {
+                    search_id: saved.id,
+                    job_id: job.id,
+                },
+            );
+        }
+    }
+
+    None
+}
+
+// called when a job is posted, sends one alert per matching saved search.
+// the first batch is checked right away, the rest from a timer.
+pub fn notify(job: &Job) {
+    let mut budget = BATCH_SIZE;
+
+    if let Some(cursor) = advance(job, 0, &mut budget) {
+        PENDING_ALERT_STORE.with(|store| store.borrow_mut().insert(job.id, cursor));
+        schedule();
+    }
+}
+
+// arms the timer while jobs are waiting for their alerts, unless it already is
+pub fn schedule() {
+    let pending = PENDING_ALERT_STORE.with(|store| !store.borrow().is_empty());
+
+    if !pending || TIMER.with(|timer| timer.get()).is_some() {
+        return;
+    }
+
+    let timer = ic_cdk_timers::set_timer(Duration::ZERO, || {
+        TIMER.with(|timer| timer.set(None));
+        flush();
+    });
+
+    TIMER.with(|cell| cell.set(Some(timer)));
+}
+
+fn flush() {
+    let mut budget = BATCH_SIZE;
+    let pending = PENDING_ALERT_STORE.with(|store| store.borrow().clone());
+
+    for (job_id, cursor) in pending {
+        if budget == 0 {
+            break;
+        }
+
+        // jobs closed in the meantime don't alert anyone anymore
+        let next = JOB_STORE
+            .with(|store| store.borrow().get(&job_id).cloned())
+            .filter(|job| job.status == JobStatus::Open)
+            .and_then(|job| advance(&job, cursor, &mut budget));
+
+        PENDING_ALERT_STORE.with(|store| match next {
+            Some(cursor) => store.borrow_mut().insert(job_id, cursor),
+            None => store.borrow_mut().remove(&job_id),
+        });
+    }
+
+    schedule();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Skill, SKILL_STORE};
+
+    fn owner(byte: u8) -> Principal {
+        Principal::from_slice(&[byte])
+    }
+
+    fn save(id: u64, skill_ids: &[u16], min_bounty: Option<u64>, keywords: &[&str]) {
+        let saved = SavedSearch {
+            id,
+            owner: owner(1),
+            name: format!("search {id}"),
+            skill_ids: skill_ids.to_vec(),
+            min_bounty,
+            keywords: keywords.iter().map(|keyword| keyword.to_string()).collect(),
+            created_at: 0,
+            updated_at: 0,
+        };
+
+        SAVED_SEARCH_STORE.with(|store| store.borrow_mut().insert(id, saved));
+        rebuild();
+    }
+
+    fn job(skill_ids: &[u16], bounty: u64, position: &str) -> Job {
+        Job {
+            id: 1,
+            position: position.to_string(),
+            bounty,
+            required_skills: skill_ids
+                .iter()
+                .map(|skill_id| (*skill_id, Default::default()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn is_match(id: u64, job: &Job) -> bool {
+        let words = search::tokenize(&job.position).into_iter().collect();
+
+        matches(&get(id).unwrap(), job, &words)
+    }
+
+    #[test]
+    fn a_search_matches_on_skills_bounty_and_every_keyword() {
+        save(1, &[1], Some(50), &["rust", "remote"]);
+
+        assert!(is_match(1, &job(&[1], 50, "remote rust engineer")));
+        assert!(!is_match(1, &job(&[1], 49, "remote rust engineer")));
+        assert!(!is_match(1, &job(&[2], 50, "remote rust engineer")));
+        assert!(!is_match(1, &job(&[1], 50, "rust engineer")));
+    }
+
+    #[test]
+    fn a_saved_skill_matches_the_skills_below_it() {
+        skills::SKILL_PARENT_STORE.with(|store| store.borrow_mut().insert(2, 1));
+        skills::rebuild();
+        save(1, &[1], None, &[]);
+
+        assert!(is_match(1, &job(&[2], 0, "")));
+    }
+
+    #[test]
+    fn a_search_needs_skills_or_keywords() {
+        let params = SavedSearchParams {
+            name: "nothing".to_string(),
+            keywords: "the and of".to_string(),
+            ..Default::default()
+        };
+        assert!(validate(params).is_err());
+
+        let params = SavedSearchParams {
+            name: "unknown skill".to_string(),
+            skill_ids: vec![7],
+            ..Default::default()
+        };
+        assert!(validate(params).is_err());
+
+        SKILL_STORE.with(|store| {
+            store.borrow_mut().insert(
+                7,
+                Skill {
+                    id: Some(7),
+                    name: "rust".to_string(),
+                },
+            )
+        });
+        let params = SavedSearchParams {
+            name: " rust jobs ".to_string(),
+            skill_ids: vec![7, 7],
+            keywords: "Remote, the remote".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            validate(params).unwrap(),
+            ("rust jobs".to_string(), vec![7], vec!["remote".to_string()])
+        );
+    }
+
+    #[test]
+    fn a_merge_moves_searches_to_the_surviving_skill() {
+        save(1, &[1, 2], None, &[]);
+
+        retarget(1, 3);
+
+        assert_eq!(get(1).unwrap().skill_ids, vec![2, 3]);
+        assert_eq!(skills_in_use(), BTreeSet::from([2, 3]));
+    }
+
+    #[test]
+    fn only_the_owner_deletes_a_search() {
+        save(1, &[1], None, &[]);
+
+        assert!(delete(owner(2), 1).is_err());
+        assert_eq!(delete(owner(1), 1), Ok(()));
+        assert!(of_owner(&owner(1)).is_empty());
+        assert!(skills_in_use().is_empty());
+    }
+
+    #[test]
+    fn candidates_are_checked_in_batches() {
+        // every search is a candidate through its skill, none of them matches the bounty
+        for id in 1..=3 {
+            save(id, &[1], Some(100), &[]);
+        }
+        let job = job(&[1], 0, "");
+
+        assert_eq!(candidates(&job).0, BTreeSet::from([1, 2, 3]));
+
+        let mut budget = 2;
+        assert_eq!(advance(&job, 0, &mut budget), Some(2));
+        assert_eq!(budget, 0);
+
+        let mut budget = 2;
+        assert_eq!(advance(&job, 2, &mut budget), None);
+        assert_eq!(budget, 1);
+    }
+}
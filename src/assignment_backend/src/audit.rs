@@ -0,0 +1,226 @@
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_cdk::api::time;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{StableBTreeMap, StableLog, Storable};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+use crate::events;
+use crate::pagination::{collect, paginate_ids};
+use crate::storage::{get_memory, Memory};
+use crate::{ApplicationStatus, BoardResult, JobStatus, Page, PageRequest, SortDirection};
+
+// the log lives in stable memory directly, so it isn't part of the upgrade snapshot
+const LOG_INDEX_MEMORY_ID: MemoryId = MemoryId::new(1);
+const LOG_DATA_MEMORY_ID: MemoryId = MemoryId::new(2);
+const BY_ENTITY_MEMORY_ID: MemoryId = MemoryId::new(3);
+const BY_CALLER_MEMORY_ID: MemoryId = MemoryId::new(4);
+
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub enum AuditEntity {
+    Job(u64),
+    Application(u64),
+    ApplicantProfile(Principal),
+    CompanyProfile(Principal),
+    Skill(u16),
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub enum AuditAction {
+    Created,
+    Updated,
+    StatusChanged,
+    Deleted,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub enum AuditStatus {
+    Job(JobStatus),
+    Application(ApplicationStatus),
+}
+
+// one write to a job, application, profile or skill. events are never changed or removed.
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct AuditEvent {
+    pub seq: u64,
+    pub caller: Principal,
+    pub timestamp: u64,
+    pub entity: AuditEntity,
+    pub action: AuditAction,
+    pub before: Option<AuditStatus>,
+    pub after: Option<AuditStatus>,
+}
+
+// a filter left empty matches every event
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct AuditFilter {
+    pub entity: Option<AuditEntity>,
+    pub caller: Option<Principal>,
+}
+
+impl Storable for AuditEvent {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).expect("failed to encode audit event"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).expect("failed to decode audit event")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// an entity as an index key: a tag byte, then the id or the principal's bytes
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct EntityKey(Vec<u8>);
+
+impl From<&AuditEntity> for EntityKey {
+    fn from(entity: &AuditEntity) -> Self {
+        let (tag, bytes) = match entity {
+            AuditEntity::Job(id) => (0, id.to_be_bytes().to_vec()),
+            AuditEntity::Application(id) => (1, id.to_be_bytes().to_vec()),
+            AuditEntity::ApplicantProfile(principal) => (2, principal.as_slice().to_vec()),
+            AuditEntity::CompanyProfile(principal) => (3, principal.as_slice().to_vec()),
+            AuditEntity::Skill(id) => (4, id.to_be_bytes().to_vec()),
+        };
+
+        Self([vec![tag], bytes].concat())
+    }
+}
+
+impl Storable for EntityKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(bytes.into_owned())
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 1 + Principal::MAX_LENGTH_IN_BYTES as u32,
+        is_fixed_size: false,
+    };
+}
+
+thread_local! {
+    static AUDIT_LOG: RefCell<StableLog<AuditEvent, Memory, Memory>> = RefCell::new(
+        StableLog::init(get_memory(LOG_INDEX_MEMORY_ID), get_memory(LOG_DATA_MEMORY_ID))
+            .expect("failed to initialize the audit log"),
+    );
+
+    // (entity, seq) and (caller, seq), so one entity's or one caller's events are a range scan
+    static AUDIT_BY_ENTITY: RefCell<StableBTreeMap<(EntityKey, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(BY_ENTITY_MEMORY_ID)));
+    static AUDIT_BY_CALLER: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(get_memory(BY_CALLER_MEMORY_ID)));
+}
+
+fn append(
+    entity: AuditEntity,
+    action: AuditAction,
+    before: Option<AuditStatus>,
+    after: Option<AuditStatus>,
+) {
+    let caller = ic_cdk::api::caller();
+    let key = EntityKey::from(&entity);
+
+    AUDIT_LOG.with(|log| {
+        let log = log.borrow();
+        let seq = log.len();
+
+        let event = AuditEvent {
+            seq,
+            caller,
+            timestamp: time(),
+            entity,
+            action,
+            before,
+            after,
+        };
+
+        log.append(&event)
+            .expect("failed to append to the audit log");
+        AUDIT_BY_ENTITY.with(|index| index.borrow_mut().insert((key, seq), ()));
+        AUDIT_BY_CALLER.with(|index| index.borrow_mut().insert((caller, seq), ()));
+        events::chain(&event);
+    });
+}
+
+// records a write of a record with a status, `before` is None when it was just created
+pub fn record(entity: AuditEntity, before: Option<AuditStatus>, after: AuditStatus) {
+    let action = match &before {
+        None => AuditAction::Created,
+        Some(before) if *before != after => AuditAction::StatusChanged,
+        Some(_) => AuditAction::Updated,
+    };
+
+    append(entity, action, before, Some(after));
+}
+
+// records a write of a profile or skill, which have no status
+pub fn record_change(entity: AuditEntity, action: AuditAction) {
+    append(entity, action, None, None);
+}
+
+pub(crate) fn get(seq: u64) -> Option<AuditEvent> {
+    AUDIT_LOG.with(|log| log.borrow().get(seq))
+}
+
+pub(crate) fn len() -> u64 {
+    AUDIT_LOG.with(|log| log.borrow().len())
+}
+
+// every seq indexed under `key`
+fn scan<K: Storable + Ord + Clone>(
+    index: &StableBTreeMap<(K, u64), (), Memory>,
+    key: K,
+) -> BTreeSet<u64> {
+    index
+        .range((key.clone(), 0)..=(key, u64::MAX))
+        .map(|((_, seq), _)| seq)
+        .collect()
+}
+
+pub fn query(filter: &AuditFilter, request: &PageRequest) -> BoardResult<Page<AuditEvent>> {
+    // the entity index narrows the most, the caller is then checked on each event
+    let seqs = match (&filter.entity, filter.caller) {
+        (Some(entity), caller) => {
+            let seqs = AUDIT_BY_ENTITY.with(|index| scan(&index.borrow(), EntityKey::from(entity)));
+
+            match caller {
+                Some(caller) => seqs
+                    .into_iter()
+                    .filter(|seq| get(*seq).is_some_and(|event| event.caller == caller))
+                    .collect(),
+                None => seqs,
+            }
+        }
+        (None, Some(caller)) => AUDIT_BY_CALLER.with(|index| scan(&index.borrow(), caller)),
+        (None, None) => return all(request),
+    };
+
+    paginate_ids(&seqs, request, |seq| get(*seq))
+}
+
+// the whole log is paged straight off stable memory, without collecting its seqs first
+fn all(request: &PageRequest) -> BoardResult<Page<AuditEvent>> {
+    let cursor = request.cursor::<u64>()?;
+    let len = len();
+
+    let seqs: Box<dyn Iterator<Item = u64>> = match (request.direction(), len) {
+        (_, 0) => Box::new(std::iter::empty()),
+        (SortDirection::Ascending, _) => Box::new(cursor.unwrap_or(0)..len),
+        (SortDirection::Descending, _) => {
+            Box::new((0..=cursor.unwrap_or(u64::MAX).min(len - 1)).rev())
+        }
+    };
+
+    Ok(collect(
+        seqs.filter_map(|seq| get(seq).map(|event| (seq, event))),
+        request.limit(),
+        len as usize,
+    ))
+}
@@ -0,0 +1,289 @@
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::time;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::skills;
+use crate::{BoardError, BoardResult, APPLICANT_PROFILE_STORE};
+
+// peers may endorse this many skills per rolling day
+pub const MAX_ENDORSEMENTS_PER_DAY: usize = 10;
+const DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+// how much one endorser and one attesting company add to the weight of a skill
+pub const ENDORSEMENT_WEIGHT: u64 = 1;
+pub const ATTESTATION_WEIGHT: u64 = 5;
+
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub enum EndorsementKind {
+    // another applicant vouches for the skill
+    Endorsement,
+    // the company that hired the applicant confirms the skill
+    Attestation {
+        company_id: Principal,
+        application_id: u64,
+    },
+}
+
+// endorsements are never edited or removed once issued
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct Endorsement {
+    pub id: u64,
+    pub applicant_id: Principal,
+    // the skill as it was endorsed, a merge later on is resolved with skills::current
+    pub skill_id: u16,
+    pub issuer: Principal,
+    pub kind: EndorsementKind,
+    pub created_at: u64,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct SkillWeight {
+    pub skill_id: u16,
+    pub endorsements: u64,
+    pub attestations: u64,
+    pub weight: u64,
+}
+
+pub type EndorsementStore = BTreeMap<u64, Endorsement>;
+
+thread_local! {
+    pub(crate) static ENDORSEMENT_ID_STORE: Cell<u64> = const { Cell::new(0) };
+    pub(crate) static ENDORSEMENT_STORE: RefCell<EndorsementStore> = RefCell::default();
+
+    // applicant -> endorsement ids and issuer -> endorsement ids, derived from ENDORSEMENT_STORE
+    static ENDORSEMENT_BY_APPLICANT_STORE: RefCell<BTreeMap<Principal, BTreeSet<u64>>> = RefCell::default();
+    static ENDORSEMENT_BY_ISSUER_STORE: RefCell<BTreeMap<Principal, BTreeSet<u64>>> = RefCell::default();
+}
+
+fn index(endorsement: &Endorsement) {
+    ENDORSEMENT_BY_APPLICANT_STORE.with(|store| {
+        store
+            .borrow_mut()
+            .entry(endorsement.applicant_id)
+            .or_default()
+            .insert(endorsement.id);
+    });
+    ENDORSEMENT_BY_ISSUER_STORE.with(|store| {
+        store
+            .borrow_mut()
+            .entry(endorsement.issuer)
+            .or_default()
+            .insert(endorsement.id);
+    });
+}
+
+pub fn rebuild() {
+    ENDORSEMENT_BY_APPLICANT_STORE.with(|store| store.borrow_mut().clear());
+    ENDORSEMENT_BY_ISSUER_STORE.with(|store| store.borrow_mut().clear());
+
+    ENDORSEMENT_STORE.with(|store| store.borrow().values().for_each(index));
+}
+
+pub fn of_applicant(applicant_id: &Principal) -> BTreeSet<u64> {
+    ENDORSEMENT_BY_APPLICANT_STORE
+        .with(|store| store.borrow().get(applicant_id).cloned())
+        .unwrap_or_default()
+}
+
+fn by_issuer(issuer: &Principal) -> Vec<Endorsement> {
+    let ids = ENDORSEMENT_BY_ISSUER_STORE
+        .with(|store| store.borrow().get(issuer).cloned())
+        .unwrap_or_default();
+
+    ENDORSEMENT_STORE.with(|store| {
+        let store = store.borrow();
+        ids.iter().filter_map(|id| store.get(id).cloned()).collect()
+    })
+}
+
+fn insert(
+    applicant_id: Principal,
+    skill_id: u16,
+    issuer: Principal,
+    kind: EndorsementKind,
+) -> Endorsement {
+    let id = ENDORSEMENT_ID_STORE.with(|id| {
+        id.set(id.get() + 1);
+        id.get()
+    });
+
+    let endorsement = Endorsement {
+        id,
+        applicant_id,
+        skill_id,
+        issuer,
+        kind,
+        created_at: time(),
+    };
+
+    ENDORSEMENT_STORE.with(|store| store.borrow_mut().insert(id, endorsement.clone()));
+    index(&endorsement);
+
+    endorsement
+}
+
+// the caller has to be an applicant, the skill has to be on the other applicant's profile
+pub fn endorse(
+    issuer: Principal,
+    applicant_id: Principal,
+    skill_id: u16,
+) -> BoardResult<Endorsement> {
+    if issuer == applicant_id {
+        return Err(BoardError::validation(
+            "applicant",
+            "can't endorse yourself",
+        ));
+    }
+
+    let listed = APPLICANT_PROFILE_STORE.with(|store| {
+        store
+            .borrow()
+            .get(&applicant_id)
+            .is_some_and(|profile| profile.skills.contains_key(&skill_id))
+    });
+
+    if !listed {
+        return Err(BoardError::not_found("skill on profile", skill_id));
+    }
+
+    let issued = by_issuer(&issuer);
+
+    let already_endorsed = issued.iter().any(|endorsement| {
+        endorsement.applicant_id == applicant_id
+            && skills::current(endorsement.skill_id) == skill_id
+            && endorsement.kind == EndorsementKind::Endorsement
+    });
+
+    if already_endorsed {
+        return Err(BoardError::conflict("Skill is already endorsed"));
+    }
+
+    let since = time().saturating_sub(DAY);
+    let recent = issued
+        .iter()
+        .filter(|endorsement| {
+            endorsement.kind == EndorsementKind::Endorsement && endorsement.created_at > since
+        })
+        .count();
+
+    if recent >= MAX_ENDORSEMENTS_PER_DAY {
+        return Err(BoardError::conflict(
+            "Endorsement limit reached, try again later",
+        ));
+    }
+
+    Ok(insert(
+        applicant_id,
+        skill_id,
+        issuer,
+        EndorsementKind::Endorsement,
+    ))
+}
+
+// attests every skill once per hire, skills attested before are skipped.
+// only the skills in `allowed`, the ones the job asked for or the hire listed, can be attested.
+pub fn attest(
+    issuer: Principal,
+    company_id: Principal,
+    application_id: u64,
+    applicant_id: Principal,
+    skill_ids: Vec<u16>,
+    allowed: &BTreeSet<u16>,
+) -> BoardResult<Vec<Endorsement>> {
+    if skill_ids.len() > skills::MAX_SKILLS_PER_REQUEST {
+        return Err(BoardError::validation("skills", "too many skills"));
+    }
+
+    if let Some(skill_id) = skill_ids.iter().find(|id| !allowed.contains(id)) {
+        return Err(BoardError::not_found("skill on job or profile", skill_id));
+    }
+
+    let kind = EndorsementKind::Attestation {
+        company_id,
+        application_id,
+    };
+
+    let attested: BTreeSet<u16> = ENDORSEMENT_STORE.with(|store| {
+        let store = store.borrow();
+
+        of_applicant(&applicant_id)
+            .iter()
+            .filter_map(|id| store.get(id))
+            .filter(|endorsement| endorsement.kind == kind)
+            .map(|endorsement| skills::current(endorsement.skill_id))
+            .collect()
+    });
+
+    let skill_ids: BTreeSet<u16> = skill_ids.into_iter().collect();
+
+    Ok(skill_ids
+        .difference(&attested)
+        .map(|skill_id| insert(applicant_id, *skill_id, issuer, kind.clone()))
+        .collect())
+}
+
+// distinct endorsers and distinct attesting companies, per skill
+pub fn weights(applicant_id: &Principal) -> BTreeMap<u16, SkillWeight> {
+    let mut endorsers = BTreeMap::<u16, BTreeSet<Principal>>::new();
+    let mut companies = BTreeMap::<u16, BTreeSet<Principal>>::new();
+
+    ENDORSEMENT_STORE.with(|store| {
+        let store = store.borrow();
+
+        for endorsement in of_applicant(applicant_id)
+            .iter()
+            .filter_map(|id| store.get(id))
+        {
+            // endorsements of a merged skill count for the skill it was merged into
+            let skill_id = skills::current(endorsement.skill_id);
+
+            match endorsement.kind {
+                EndorsementKind::Endorsement => endorsers
+                    .entry(skill_id)
+                    .or_default()
+                    .insert(endorsement.issuer),
+                EndorsementKind::Attestation { company_id, .. } => {
+                    companies.entry(skill_id).or_default().insert(company_id)
+                }
+            };
+        }
+    });
+
+    let skill_ids: BTreeSet<u16> = endorsers.keys().chain(companies.keys()).cloned().collect();
+
+    skill_ids
+        .into_iter()
+        .map(|skill_id| {
+            let endorsements = endorsers.get(&skill_id).map_or(0, |set| set.len() as u64);
+            let attestations = companies.get(&skill_id).map_or(0, |set| set.len() as u64);
+
+            let weight = SkillWeight {
+                skill_id,
+                endorsements,
+                attestations,
+                weight: endorsements * ENDORSEMENT_WEIGHT + attestations * ATTESTATION_WEIGHT,
+            };
+            (skill_id, weight)
+        })
+        .collect()
+}
+
+// the total weight of an applicant's skills, for rankings
+pub fn weight(applicant_id: &Principal, skill_ids: &BTreeSet<u16>) -> u64 {
+    weights(applicant_id)
+        .values()
+        .filter(|weight| skill_ids.contains(&weight.skill_id))
+        .map(|weight| weight.weight)
+        .sum()
+}
+
+pub fn skills_in_use() -> BTreeSet<u16> {
+    ENDORSEMENT_STORE.with(|store| {
+        store
+            .borrow()
+            .values()
+            .map(|endorsement| skills::current(endorsement.skill_id))
+            .collect()
+    })
+}
@@ -0,0 +1,46 @@
+use candid::{CandidType, Deserialize};
+
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub enum BoardError {
+    NotFound(String),
+    Unauthorized(String),
+    InvalidTransition { from: String, to: String },
+    Validation { field: String, reason: String },
+    Conflict(String),
+    Internal(String),
+    Ledger(String),
+}
+
+impl BoardError {
+    pub fn not_found(entity: &str, id: impl ToString) -> Self {
+        Self::NotFound(format!("{} {}", entity, id.to_string()))
+    }
+
+    pub fn unauthorized(reason: &str) -> Self {
+        Self::Unauthorized(String::from(reason))
+    }
+
+    pub fn invalid_transition(from: impl std::fmt::Debug, to: impl std::fmt::Debug) -> Self {
+        Self::InvalidTransition {
+            from: format!("{:?}", from),
+            to: format!("{:?}", to),
+        }
+    }
+
+    pub fn validation(field: &str, reason: &str) -> Self {
+        Self::Validation {
+            field: String::from(field),
+            reason: String::from(reason),
+        }
+    }
+
+    pub fn conflict(reason: &str) -> Self {
+        Self::Conflict(String::from(reason))
+    }
+
+    pub fn ledger(reason: impl ToString) -> Self {
+        Self::Ledger(reason.to_string())
+    }
+}
+
+pub type BoardResult<T> = Result<T, BoardError>;
@@ -0,0 +1,459 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::api::time;
+use icrc_ledger_types::icrc1::account::{Account, Subaccount};
+use icrc_ledger_types::icrc1::transfer::{Memo, TransferArg, TransferError};
+use icrc_ledger_types::icrc2::transfer_from::{TransferFromArgs, TransferFromError};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+
+use crate::{BoardError, BoardResult};
+
+// the ICRC-1 ledger bounties are paid in, set with the init (or upgrade) args
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct LedgerConfig {
+    pub canister_id: Principal,
+    pub token_symbol: String,
+    pub fee: u64,
+}
+
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, PartialEq)]
+pub enum Payout {
+    Release,
+    Refund,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub enum EscrowStatus {
+    Held,
+    // the transfer out keeps its `created_at_time`, so a retry is deduplicated by the ledger.
+    // `error` is set once an attempt failed.
+    Paying {
+        payout: Payout,
+        to: Principal,
+        created_at_time: u64,
+        error: Option<String>,
+    },
+    Paid {
+        payout: Payout,
+        to: Principal,
+        block_index: Nat,
+    },
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct Escrow {
+    pub job_id: u64,
+    pub amount: u64,
+    pub funded_by: Principal,
+    pub funding_block: Nat,
+    // the ledger fee when the escrow was funded, the transfer out pays it.
+    // updated if the ledger asks for another fee. None on escrows funded before it was kept.
+    pub fee: Option<u64>,
+    pub status: EscrowStatus,
+    pub updated_at: u64,
+    // the subaccount holding the bounty when it isn't the one of the job.
+    // a new job is funded before it has an id, see `fund_new`.
+    pub subaccount: Option<Subaccount>,
+}
+
+pub type EscrowStore = BTreeMap<u64, Escrow>;
+
+thread_local! {
+    pub(crate) static LEDGER_CONFIG: RefCell<Option<LedgerConfig>> = RefCell::default();
+    pub(crate) static ESCROW_STORE: RefCell<EscrowStore> = RefCell::default();
+
+    // numbers the fundings of jobs without an id, see `funding_subaccount`
+    static FUNDING_COUNT: Cell<u64> = Cell::default();
+}
+
+pub fn configure(config: LedgerConfig) {
+    LEDGER_CONFIG.with(|ledger| ledger.replace(Some(config)));
+}
+
+pub fn config() -> Option<LedgerConfig> {
+    LEDGER_CONFIG.with(|ledger| ledger.borrow().clone())
+}
+
+pub fn get(job_id: u64) -> Option<Escrow> {
+    ESCROW_STORE.with(|store| store.borrow().get(&job_id).cloned())
+}
+
+fn insert(escrow: Escrow) {
+    ESCROW_STORE.with(|store| store.borrow_mut().insert(escrow.job_id, escrow));
+}
+
+// every job gets its own subaccount of the canister, so escrows never mix
+fn subaccount(job_id: u64) -> Subaccount {
+    let mut subaccount = [0; 32];
+    subaccount[24..].copy_from_slice(&job_id.to_be_bytes());
+    subaccount
+}
+
+// the first byte keeps these apart from the subaccounts of jobs, the time and count
+// from each other, the count starts over after an upgrade but the time doesn't
+fn funding_subaccount(at: u64, count: u64) -> Subaccount {
+    let mut subaccount = [0; 32];
+    subaccount[0] = 1;
+    subaccount[8..16].copy_from_slice(&at.to_be_bytes());
+    subaccount[24..].copy_from_slice(&count.to_be_bytes());
+    subaccount
+}
+
+// where the bounty of an escrow sits
+fn source(escrow: &Escrow) -> Subaccount {
+    escrow
+        .subaccount
+        .unwrap_or_else(|| subaccount(escrow.job_id))
+}
+
+// pulls `amount` from the funder into the escrow of the job.
+// the funder has to `icrc2_approve` the canister for `amount` plus the ledger fee first.
+pub async fn fund(job_id: u64, funded_by: Principal, amount: u64) -> BoardResult<()> {
+    if let Some(escrow) = pull(funded_by, amount, subaccount(job_id), Some(job_id)).await? {
+        insert(Escrow { job_id, ..escrow });
+    }
+
+    Ok(())
+}
+
+// pulls the bounty of a job that only gets its id once the bounty is in.
+// the escrow keeps a subaccount of its own, `hold` files it under the job.
+// None when there is no bounty to escrow.
+pub async fn fund_new(funded_by: Principal, amount: u64) -> BoardResult<Option<Escrow>> {
+    if amount == 0 {
+        return Ok(None);
+    }
+
+    let count = FUNDING_COUNT.with(|count| {
+        count.set(count.get() + 1);
+        count.get()
+    });
+    let subaccount = funding_subaccount(time(), count);
+
+    let escrow = pull(funded_by, amount, subaccount, None).await?;
+
+    Ok(escrow.map(|escrow| Escrow {
+        subaccount: Some(subaccount),
+        ..escrow
+    }))
+}
+
+pub fn hold(job_id: u64, escrow: Escrow) {
+    insert(Escrow { job_id, ..escrow });
+}
+
+async fn pull(
+    funded_by: Principal,
+    amount: u64,
+    to: Subaccount,
+    memo: Option<u64>,
+) -> BoardResult<Option<Escrow>> {
+    if amount == 0 {
+        return Ok(None);
+    }
+
+    let ledger = config()
+        .ok_or_else(|| BoardError::validation("bounty", "no ledger is configured for bounties"))?;
+
+    // the escrow pays the fee of the transfer out
+    if amount <= ledger.fee {
+        return Err(BoardError::validation(
+            "bounty",
+            "must be greater than the ledger fee",
+        ));
+    }
+
+    let args = TransferFromArgs {
+        spender_subaccount: None,
+        from: Account::from(funded_by),
+        to: Account {
+            owner: ic_cdk::api::id(),
+            subaccount: Some(to),
+        },
+        amount: Nat::from(amount),
+        fee: None,
+        memo: memo.map(Memo::from),
+        created_at_time: None,
+    };
+
+    let (result,): (Result<Nat, TransferFromError>,) =
+        ic_cdk::call(ledger.canister_id, "icrc2_transfer_from", (args,))
+            .await
+            .map_err(|(_, reason)| BoardError::ledger(reason))?;
+
+    let funding_block = result.map_err(BoardError::ledger)?;
+
+    Ok(Some(Escrow {
+        job_id: 0,
+        amount,
+        funded_by,
+        funding_block,
+        fee: Some(ledger.fee),
+        status: EscrowStatus::Held,
+        updated_at: time(),
+        subaccount: None,
+    }))
+}
+
+// pays the bounty out to the hire
+pub fn release(job_id: u64, to: Principal) {
+    start(job_id, Payout::Release, Some(to));
+}
+
+// sends the bounty back to whoever funded it
+pub fn refund(job_id: u64) {
+    start(job_id, Payout::Refund, None);
+}
+
+// only a held escrow is paid out, so a job closed after a hire doesn't refund the bounty too
+fn start(job_id: u64, payout: Payout, to: Option<Principal>) {
+    let Some(escrow) = get(job_id) else {
+        return;
+    };
+
+    if !matches!(escrow.status, EscrowStatus::Held) {
+        return;
+    }
+
+    insert(Escrow {
+        status: EscrowStatus::Paying {
+            payout,
+            to: to.unwrap_or(escrow.funded_by),
+            created_at_time: time(),
+            error: None,
+        },
+        updated_at: time(),
+        ..escrow
+    });
+
+    // transitions are synchronous, the ledger call finishes after the caller got its reply
+    ic_cdk::spawn(async move {
+        let _ = pay(job_id).await;
+    });
+}
+
+// (re)tries the transfer out of an escrow that is being paid
+pub async fn pay(job_id: u64) -> BoardResult<Escrow> {
+    let escrow = get(job_id).ok_or_else(|| BoardError::not_found("escrow", job_id))?;
+
+    let EscrowStatus::Paying {
+        payout,
+        to,
+        created_at_time,
+        ..
+    } = escrow.status
+    else {
+        return Err(BoardError::conflict("Escrow is not being paid out"));
+    };
+
+    let ledger = config().ok_or_else(|| BoardError::ledger("no ledger is configured"))?;
+    let fee = escrow.fee.unwrap_or(ledger.fee);
+    let amount = escrow.amount.saturating_sub(fee);
+
+    // nothing would be left to send, the escrow stays unpaid with the reason
+    if amount == 0 {
+        let error = "the bounty doesn't cover the ledger fee";
+        insert(Escrow {
+            status: EscrowStatus::Paying {
+                payout,
+                to,
+                created_at_time,
+                error: Some(error.to_string()),
+            },
+            updated_at: time(),
+            ..escrow
+        });
+
+        return Err(BoardError::ledger(error));
+    }
+
+    let args = TransferArg {
+        from_subaccount: Some(source(&escrow)),
+        to: Account::from(to),
+        fee: Some(Nat::from(fee)),
+        created_at_time: Some(created_at_time),
+        memo: Some(Memo::from(job_id)),
+        amount: Nat::from(amount),
+    };
+
+    let result: Result<(Result<Nat, TransferError>,), _> =
+        ic_cdk::call(ledger.canister_id, "icrc1_transfer", (args,)).await;
+
+    // the ledger changed its fee since the escrow was funded, the next attempt pays that one
+    let mut fee = escrow.fee;
+    if let Ok((Err(TransferError::BadFee { expected_fee }),)) = &result {
+        fee = u64::try_from(&expected_fee.0).ok().or(fee);
+    }
+
+    let status = match result {
+        Ok((Ok(block_index),))
+        | Ok((Err(TransferError::Duplicate {
+            duplicate_of: block_index,
+        }),)) => EscrowStatus::Paid {
+            payout,
+            to,
+            block_index,
+        },
+        // outside of the deduplication window, the next attempt needs a fresh timestamp
+        Ok((Err(TransferError::TooOld),)) => EscrowStatus::Paying {
+            payout,
+            to,
+            created_at_time: time(),
+            error: Some(TransferError::TooOld.to_string()),
+        },
+        Ok((Err(error),)) => EscrowStatus::Paying {
+            payout,
+            to,
+            created_at_time,
+            error: Some(error.to_string()),
+        },
+        Err((_, reason)) => EscrowStatus::Paying {
+            payout,
+            to,
+            created_at_time,
+            error: Some(reason),
+        },
+    };
+
+    // a concurrent attempt may have finished first, a paid escrow stays paid
+    if let Some(current) = get(job_id) {
+        if matches!(current.status, EscrowStatus::Paid { .. }) {
+            return Ok(current);
+        }
+    }
+
+    let escrow = Escrow {
+        fee,
+        status,
+        updated_at: time(),
+        ..escrow
+    };
+
+    insert(escrow.clone());
+
+    Ok(escrow)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    use super::*;
+
+    // the paths under test return before the first ledger call, so one poll finishes them
+    fn ready<F: Future>(future: F) -> F::Output {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("the future is waiting on a call"),
+        }
+    }
+
+    fn funder() -> Principal {
+        Principal::from_slice(&[1])
+    }
+
+    fn ledger(fee: u64) {
+        configure(LedgerConfig {
+            canister_id: Principal::from_slice(&[9]),
+            token_symbol: "TOKEN".to_string(),
+            fee,
+        });
+    }
+
+    fn escrow(job_id: u64, status: EscrowStatus) -> Escrow {
+        Escrow {
+            job_id,
+            amount: 100,
+            funded_by: funder(),
+            funding_block: Nat::from(1u64),
+            fee: Some(10),
+            status,
+            updated_at: 0,
+            subaccount: None,
+        }
+    }
+
+    #[test]
+    fn every_job_has_its_own_subaccount() {
+        assert_ne!(subaccount(1), subaccount(2));
+        assert_eq!(subaccount(0x0102)[30..], [1, 2]);
+        assert!(subaccount(u64::MAX)[..24].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn a_bounty_funded_before_its_job_stays_in_its_own_subaccount() {
+        let funding = funding_subaccount(1, 2);
+        assert_ne!(funding, funding_subaccount(1, 3));
+        assert_ne!(funding, funding_subaccount(2, 2));
+        assert_ne!(funding, subaccount(u64::MAX));
+
+        hold(
+            3,
+            Escrow {
+                subaccount: Some(funding),
+                ..escrow(0, EscrowStatus::Held)
+            },
+        );
+
+        let held = get(3).unwrap();
+        assert_eq!(held.job_id, 3);
+        assert_eq!(source(&held), funding);
+        assert_eq!(source(&escrow(3, EscrowStatus::Held)), subaccount(3));
+    }
+
+    #[test]
+    fn a_new_job_without_a_bounty_needs_no_escrow() {
+        assert!(matches!(ready(fund_new(funder(), 0)), Ok(None)));
+    }
+
+    #[test]
+    fn a_job_without_a_bounty_needs_no_escrow() {
+        assert_eq!(ready(fund(1, funder(), 0)), Ok(()));
+        assert!(get(1).is_none());
+    }
+
+    #[test]
+    fn funding_needs_a_ledger() {
+        assert!(ready(fund(1, funder(), 100)).is_err());
+    }
+
+    #[test]
+    fn a_bounty_has_to_cover_the_ledger_fee() {
+        ledger(10);
+
+        assert!(ready(fund(1, funder(), 10)).is_err());
+        assert!(get(1).is_none());
+    }
+
+    #[test]
+    fn a_paid_escrow_is_not_paid_again() {
+        let paid = EscrowStatus::Paid {
+            payout: Payout::Release,
+            to: Principal::from_slice(&[2]),
+            block_index: Nat::from(2u64),
+        };
+        insert(escrow(1, paid));
+
+        refund(1);
+
+        assert!(matches!(
+            get(1).unwrap().status,
+            EscrowStatus::Paid {
+                payout: Payout::Release,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn only_an_escrow_being_paid_can_be_retried() {
+        ledger(10);
+        insert(escrow(1, EscrowStatus::Held));
+
+        assert!(ready(pay(1)).is_err());
+        assert!(ready(pay(2)).is_err());
+        assert!(matches!(get(1).unwrap().status, EscrowStatus::Held));
+    }
+}
@@ -0,0 +1,199 @@
+use candid::{CandidType, Deserialize, Encode};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::storable::Blob;
+use ic_stable_structures::StableVec;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+use crate::audit::{self, AuditAction, AuditEntity, AuditEvent, AuditStatus};
+use crate::storage::{get_memory, Memory};
+
+pub const DEFAULT_EVENT_LIMIT: u64 = 100;
+pub const MAX_EVENT_LIMIT: u64 = 500;
+
+// hash n is sha256(hash n-1 ++ candid encoding of event n). the hash before
+// event 0 is all zeros. hashes are kept next to the audit log, one per event.
+const CHAIN_MEMORY_ID: MemoryId = MemoryId::new(5);
+const GENESIS_HASH: [u8; 32] = [0; 32];
+
+type Hash = Blob<32>;
+
+// an audit event as indexers see it, without the caller
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct BoardEvent {
+    pub seq: u64,
+    pub timestamp: u64,
+    pub entity: AuditEntity,
+    pub action: AuditAction,
+    pub before: Option<AuditStatus>,
+    pub after: Option<AuditStatus>,
+}
+
+impl From<AuditEvent> for BoardEvent {
+    fn from(event: AuditEvent) -> Self {
+        Self {
+            seq: event.seq,
+            timestamp: event.timestamp,
+            entity: event.entity,
+            action: event.action,
+            before: event.before,
+            after: event.after,
+        }
+    }
+}
+
+// `previous_hash` chained over `events` gives the hash after the last of them.
+// once a consumer has caught up, that matches `tip_hash`, which the canister certifies.
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct EventBatch {
+    pub events: Vec<BoardEvent>,
+    pub previous_hash: Vec<u8>,
+    // the newest event, None while the log is empty
+    pub tip_seq: Option<u64>,
+    pub tip_hash: Vec<u8>,
+    // only available in query calls
+    pub certificate: Option<Vec<u8>>,
+}
+
+thread_local! {
+    static HASH_CHAIN: RefCell<StableVec<Hash, Memory>> = RefCell::new(
+        StableVec::init(get_memory(CHAIN_MEMORY_ID)).expect("failed to initialize the event hash chain"),
+    );
+}
+
+fn next_hash(previous: &[u8], event: &BoardEvent) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(previous);
+    hasher.update(Encode!(event).expect("failed to encode event"));
+
+    hasher.finalize().into()
+}
+
+// the hash after event `seq`, or the genesis hash for None
+fn hash_after(seq: Option<u64>) -> Vec<u8> {
+    seq.and_then(|seq| HASH_CHAIN.with(|chain| chain.borrow().get(seq)))
+        .map_or(GENESIS_HASH.to_vec(), |hash| hash.as_slice().to_vec())
+}
+
+fn tip_seq() -> Option<u64> {
+    HASH_CHAIN.with(|chain| chain.borrow().len()).checked_sub(1)
+}
+
+fn push(event: AuditEvent) -> [u8; 32] {
+    let hash = next_hash(&hash_after(event.seq.checked_sub(1)), &event.into());
+
+    HASH_CHAIN.with(|chain| {
+        chain
+            .borrow()
+            .push(&Hash::try_from(&hash[..]).expect("a sha256 hash is 32 bytes"))
+            .expect("failed to extend the event hash chain");
+    });
+
+    hash
+}
+
+// called by the audit log for every event it appends
+pub fn chain(event: &AuditEvent) {
+    let hash = push(event.clone());
+    ic_cdk::api::set_certified_data(&hash);
+}
+
+// certified data doesn't survive an upgrade, so post_upgrade certifies the tip again.
+// events logged before the chain existed are hashed first.
+pub fn certify() {
+    let start = HASH_CHAIN.with(|chain| chain.borrow().len());
+
+    for seq in start..audit::len() {
+        if let Some(event) = audit::get(seq) {
+            push(event);
+        }
+    }
+
+    ic_cdk::api::set_certified_data(&hash_after(tip_seq()));
+}
+
+// events from `seq` on, oldest first
+pub fn since(seq: u64, limit: Option<u64>) -> EventBatch {
+    let limit = limit
+        .unwrap_or(DEFAULT_EVENT_LIMIT)
+        .clamp(1, MAX_EVENT_LIMIT);
+    let tip_seq = tip_seq();
+    let end = tip_seq.map_or(0, |tip| tip + 1);
+    let start = seq.min(end);
+
+    EventBatch {
+        events: (start..end.min(start.saturating_add(limit)))
+            .filter_map(audit::get)
+            .map(BoardEvent::from)
+            .collect(),
+        previous_hash: hash_after(start.checked_sub(1)),
+        tip_seq,
+        tip_hash: hash_after(tip_seq),
+        certificate: ic_cdk::api::data_certificate(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::Principal;
+
+    use super::*;
+
+    fn event(seq: u64, job_id: u64) -> AuditEvent {
+        AuditEvent {
+            seq,
+            caller: Principal::anonymous(),
+            timestamp: seq,
+            entity: AuditEntity::Job(job_id),
+            action: AuditAction::Created,
+            before: None,
+            after: None,
+        }
+    }
+
+    #[test]
+    fn an_empty_chain_is_at_the_genesis_hash() {
+        assert_eq!(tip_seq(), None);
+        assert_eq!(hash_after(None), GENESIS_HASH.to_vec());
+    }
+
+    #[test]
+    fn each_hash_covers_the_one_before() {
+        let events: Vec<AuditEvent> = (0..3).map(|seq| event(seq, seq)).collect();
+        for event in &events {
+            push(event.clone());
+        }
+
+        let mut expected = GENESIS_HASH;
+        for event in events {
+            expected = next_hash(&expected, &event.into());
+        }
+
+        assert_eq!(tip_seq(), Some(2));
+        assert_eq!(hash_after(Some(2)), expected.to_vec());
+    }
+
+    #[test]
+    fn a_changed_event_changes_the_later_hashes() {
+        push(event(0, 1));
+        push(event(1, 2));
+
+        let changed = next_hash(&GENESIS_HASH, &event(0, 9).into());
+        let changed = next_hash(&changed, &event(1, 2).into());
+
+        assert_ne!(hash_after(Some(1)), changed.to_vec());
+    }
+
+    #[test]
+    fn the_caller_is_not_hashed() {
+        let other_caller = AuditEvent {
+            caller: Principal::management_canister(),
+            ..event(0, 1)
+        };
+
+        assert_eq!(
+            next_hash(&GENESIS_HASH, &event(0, 1).into()),
+            next_hash(&GENESIS_HASH, &other_caller.into())
+        );
+    }
+}
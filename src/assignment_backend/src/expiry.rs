@@ -0,0 +1,218 @@
+use ic_cdk::api::time;
+use ic_cdk_timers::TimerId;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use crate::transition::{self, Actor};
+use crate::{index, ApplicationStatus, BoardError, BoardResult, Job, JobStatus, JOB_STORE};
+
+// applications closed out per timer tick, each expiring job counts as one more
+const BATCH_SIZE: usize = 500;
+
+// expired jobs that still have pending applications to close out, see expire_due
+pub type ClosingJobStore = BTreeSet<u64>;
+
+thread_local! {
+    // set with the init (or upgrade) args, see application_status
+    pub(crate) static EXPIRED_APPLICATION_STATUS: RefCell<Option<ApplicationStatus>> = RefCell::default();
+    pub(crate) static CLOSING_JOB_STORE: RefCell<ClosingJobStore> = RefCell::default();
+
+    // one timer for the job that expires next. timers don't survive an upgrade,
+    // post_upgrade arms it again.
+    static TIMER: Cell<Option<TimerId>> = const { Cell::new(None) };
+
+    // expired jobs being funded again, so two reopens don't both escrow the bounty
+    static REOPENING: RefCell<BTreeSet<u64>> = RefCell::default();
+}
+
+// only statuses that end an application can be set, init traps on anything else
+pub fn configure(status: ApplicationStatus) {
+    if !matches!(
+        status,
+        ApplicationStatus::Rejected | ApplicationStatus::Withdraw
+    ) {
+        ic_cdk::trap("expired_application_status must be Rejected or Withdraw");
+    }
+
+    EXPIRED_APPLICATION_STATUS.with(|current| current.replace(Some(status)));
+}
+
+// what an application still waiting for an answer becomes when its job expires.
+// offers already made are rejected either way.
+pub fn application_status() -> ApplicationStatus {
+    EXPIRED_APPLICATION_STATUS
+        .with(|status| status.borrow().clone())
+        .unwrap_or(ApplicationStatus::Rejected)
+}
+
+pub fn validate(expires_at: u64) -> BoardResult<()> {
+    if expires_at <= time() {
+        return Err(BoardError::validation(
+            "expires_at",
+            "must be in the future",
+        ));
+    }
+
+    Ok(())
+}
+
+// arms the timer for the job that expires next, replacing the one armed before
+pub fn schedule() {
+    if let Some(timer) = TIMER.with(|timer| timer.take()) {
+        ic_cdk_timers::clear_timer(timer);
+    }
+
+    // jobs that are still closing out their applications carry on right away
+    let closing = CLOSING_JOB_STORE.with(|store| !store.borrow().is_empty());
+
+    let delay = match index::next_expiry() {
+        _ if closing => Duration::ZERO,
+        Some(expires_at) => Duration::from_nanos(expires_at.saturating_sub(time())),
+        None => return,
+    };
+
+    let timer = ic_cdk_timers::set_timer(delay, || {
+        TIMER.with(|timer| timer.set(None));
+        expire_due();
+    });
+
+    TIMER.with(|cell| cell.set(Some(timer)));
+}
+
+// closes the jobs that are due and their pending applications, a batch at a time. the
+// timer is armed again right away while work is left, so every tick stays well inside the
+// instruction limit. a job with more applications than fit in a tick finishes in later ones.
+fn expire_due() {
+    let mut budget = BATCH_SIZE;
+
+    let closing = CLOSING_JOB_STORE.with(|store| store.borrow().clone());
+    for job_id in closing {
+        if budget == 0 {
+            break;
+        }
+
+        let done = JOB_STORE
+            .with(|store| store.borrow().get(&job_id).cloned())
+            .is_none_or(|job| transition::close_out(&job, &mut budget));
+
+        if done {
+            CLOSING_JOB_STORE.with(|store| store.borrow_mut().remove(&job_id));
+        }
+    }
+
+    for job_id in index::jobs_expiring_by(time()) {
+        if budget == 0 {
+            break;
+        }
+        budget -= 1;
+
+        let Some(job) = JOB_STORE.with(|store| store.borrow().get(&job_id).cloned()) else {
+            continue;
+        };
+
+        let job = Job {
+            expired_at: Some(time()),
+            ..job
+        };
+
+        // the job leaves the expiry index once it is closed, so a failure isn't retried.
+        // the only way to fail is the job not being open anymore.
+        if transition::job_within(job, JobStatus::Closed, Actor::System, &mut budget).is_ok()
+            && budget == 0
+        {
+            CLOSING_JOB_STORE.with(|store| store.borrow_mut().insert(job_id));
+        }
+    }
+
+    schedule();
+}
+
+// moves the expiry of an open job, None keeps it open until it is closed by hand
+pub fn extend(job: Job, expires_at: Option<u64>) -> BoardResult<Job> {
+    if job.status != JobStatus::Open {
+        return Err(BoardError::conflict("Only open jobs can be extended"));
+    }
+
+    if let Some(expires_at) = expires_at {
+        validate(expires_at)?;
+    }
+
+    let job = Job {
+        expires_at,
+        updated_at: time(),
+        ..job
+    };
+
+    index::insert_job(job.clone());
+    schedule();
+
+    Ok(job)
+}
+
+// marks an expired job as being reopened, until `finish_reopen`
+pub fn start_reopen(job: &Job) -> BoardResult<()> {
+    if job.expired_at.is_none() {
+        return Err(BoardError::conflict("Only expired jobs can be reopened"));
+    }
+
+    if !REOPENING.with(|reopening| reopening.borrow_mut().insert(job.id)) {
+        return Err(BoardError::conflict("Job is already being reopened"));
+    }
+
+    Ok(())
+}
+
+pub fn finish_reopen(job_id: u64) {
+    REOPENING.with(|reopening| reopening.borrow_mut().remove(&job_id));
+    schedule();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(status: JobStatus, expired_at: Option<u64>) -> Job {
+        Job {
+            id: 1,
+            status,
+            expired_at,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn waiting_applications_are_rejected_unless_configured() {
+        assert_eq!(application_status(), ApplicationStatus::Rejected);
+
+        configure(ApplicationStatus::Withdraw);
+        assert_eq!(application_status(), ApplicationStatus::Withdraw);
+    }
+
+    #[test]
+    fn only_open_jobs_are_extended() {
+        assert!(extend(job(JobStatus::Closed, None), None).is_err());
+        assert!(extend(job(JobStatus::Canceled, None), Some(1)).is_err());
+    }
+
+    #[test]
+    fn an_expired_job_is_reopened_once_at_a_time() {
+        assert!(start_reopen(&job(JobStatus::Closed, None)).is_err());
+
+        let expired = job(JobStatus::Closed, Some(5));
+        assert_eq!(start_reopen(&expired), Ok(()));
+        assert!(start_reopen(&expired).is_err());
+
+        finish_reopen(expired.id);
+        assert_eq!(start_reopen(&expired), Ok(()));
+    }
+
+    #[test]
+    fn only_the_company_reopens_and_only_expired_jobs() {
+        let closed = job(JobStatus::Closed, None);
+        assert!(transition::job(closed, JobStatus::Open, Actor::Company).is_err());
+
+        let expired = job(JobStatus::Closed, Some(5));
+        assert!(transition::job(expired, JobStatus::Open, Actor::System).is_err());
+    }
+}
@@ -0,0 +1,317 @@
+use candid::{CandidType, Deserialize, Principal};
+use std::collections::BTreeSet;
+
+use crate::{index, skills};
+use crate::{BoardError, BoardResult, JobResponse, JobStatus, Page};
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize, PartialEq)]
+pub enum SkillMatch {
+    // jobs asking for at least one of the skills
+    #[default]
+    Any,
+    // jobs asking for every one of the skills
+    All,
+}
+
+// every field left empty matches all jobs, ranges include both ends
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct JobFilter {
+    pub statuses: Option<Vec<JobStatus>>,
+    pub company_id: Option<Principal>,
+    // a skill also matches the skills below it in the taxonomy
+    pub skill_ids: Option<Vec<u16>>,
+    pub skill_match: Option<SkillMatch>,
+    pub bounty_min: Option<u64>,
+    pub bounty_max: Option<u64>,
+    pub created_from: Option<u64>,
+    pub created_to: Option<u64>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct StatusCount {
+    pub status: JobStatus,
+    pub count: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct SkillCount {
+    pub skill_id: u16,
+    pub count: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CompanyCount {
+    pub company_id: Principal,
+    pub count: u64,
+}
+
+// counts over every job passing the filter, not only the ones on the page
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct JobFacets {
+    pub statuses: Vec<StatusCount>,
+    pub skills: Vec<SkillCount>,
+    pub companies: Vec<CompanyCount>,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct FilteredJobs {
+    pub page: Page<JobResponse>,
+    pub facets: JobFacets,
+}
+
+fn intersect(current: Option<BTreeSet<u64>>, ids: BTreeSet<u64>) -> Option<BTreeSet<u64>> {
+    Some(match current {
+        Some(current) => current.intersection(&ids).cloned().collect(),
+        None => ids,
+    })
+}
+
+fn jobs_by_skill_tree(skill_id: u16) -> BTreeSet<u64> {
+    skills::subtree(skill_id)
+        .iter()
+        .flat_map(index::jobs_by_skill)
+        .collect()
+}
+
+fn validate(filter: &JobFilter) -> BoardResult<()> {
+    if let (Some(min), Some(max)) = (filter.bounty_min, filter.bounty_max) {
+        if min > max {
+            return Err(BoardError::validation("bounty_min", "is above bounty_max"));
+        }
+    }
+
+    if let (Some(from), Some(to)) = (filter.created_from, filter.created_to) {
+        if from > to {
+            return Err(BoardError::validation(
+                "created_from",
+                "is after created_to",
+            ));
+        }
+    }
+
+    if filter
+        .skill_ids
+        .as_ref()
+        .is_some_and(|skill_ids| skill_ids.len() > skills::MAX_SKILLS_PER_REQUEST)
+    {
+        return Err(BoardError::validation("skill_ids", "too many skills"));
+    }
+
+    Ok(())
+}
+
+// ids of the jobs passing the filter, read from the secondary indexes.
+// `None` when the filter doesn't restrict anything.
+pub fn candidates(filter: &JobFilter) -> BoardResult<Option<BTreeSet<u64>>> {
+    validate(filter)?;
+
+    let mut ids = None;
+
+    if let Some(statuses) = &filter.statuses {
+        ids = intersect(
+            ids,
+            statuses.iter().flat_map(index::jobs_by_status).collect(),
+        );
+    }
+
+    if let Some(company_id) = &filter.company_id {
+        ids = intersect(ids, index::jobs_by_company(company_id));
+    }
+
+    if let Some(skill_ids) = &filter.skill_ids {
+        match filter.skill_match.clone().unwrap_or_default() {
+            SkillMatch::Any => {
+                let matches = skill_ids.iter().flat_map(|id| jobs_by_skill_tree(*id));
+                ids = intersect(ids, matches.collect());
+            }
+            SkillMatch::All => {
+                for skill_id in skill_ids {
+                    ids = intersect(ids, jobs_by_skill_tree(*skill_id));
+                }
+            }
+        }
+    }
+
+    if filter.bounty_min.is_some() || filter.bounty_max.is_some() {
+        let range = filter.bounty_min.unwrap_or(0)..=filter.bounty_max.unwrap_or(u64::MAX);
+        ids = intersect(ids, index::jobs_by_bounty(range));
+    }
+
+    if filter.created_from.is_some() || filter.created_to.is_some() {
+        let range = filter.created_from.unwrap_or(0)..=filter.created_to.unwrap_or(u64::MAX);
+        ids = intersect(ids, index::jobs_created_between(range));
+    }
+
+    Ok(ids)
+}
+
+pub fn facets(candidates: Option<&BTreeSet<u64>>) -> JobFacets {
+    JobFacets {
+        statuses: index::job_status_facets(candidates)
+            .into_iter()
+            .map(|(status, count)| StatusCount { status, count })
+            .collect(),
+        skills: index::job_skill_facets(candidates)
+            .into_iter()
+            .map(|(skill_id, count)| SkillCount { skill_id, count })
+            .collect(),
+        companies: index::job_company_facets(candidates)
+            .into_iter()
+            .map(|(company_id, count)| CompanyCount { company_id, count })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{Job, JOB_STORE};
+
+    fn company(byte: u8) -> Principal {
+        Principal::from_slice(&[byte])
+    }
+
+    fn job(id: u64, company_byte: u8, status: JobStatus, bounty: u64, skill_ids: &[u16]) -> Job {
+        Job {
+            id,
+            company_id: Some(company(company_byte)),
+            status,
+            bounty,
+            created_at: id * 10,
+            required_skills: skill_ids
+                .iter()
+                .map(|skill_id| (*skill_id, Default::default()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    // created_at is ten times the id
+    fn sample() {
+        JOB_STORE.with(|store| {
+            let mut store = store.borrow_mut();
+            store.insert(1, job(1, 1, JobStatus::Open, 100, &[1, 2]));
+            store.insert(2, job(2, 1, JobStatus::Closed, 200, &[1]));
+            store.insert(3, job(3, 2, JobStatus::Open, 300, &[2]));
+        });
+
+        index::rebuild();
+    }
+
+    fn ids(filter: JobFilter) -> Option<Vec<u64>> {
+        candidates(&filter)
+            .unwrap()
+            .map(|ids| ids.into_iter().collect())
+    }
+
+    #[test]
+    fn an_empty_filter_matches_everything() {
+        sample();
+
+        assert_eq!(ids(JobFilter::default()), None);
+    }
+
+    #[test]
+    fn fields_narrow_each_other_down() {
+        sample();
+
+        let filter = JobFilter {
+            statuses: Some(vec![JobStatus::Open]),
+            company_id: Some(company(1)),
+            ..Default::default()
+        };
+        assert_eq!(ids(filter), Some(vec![1]));
+    }
+
+    #[test]
+    fn ranges_include_both_ends() {
+        sample();
+
+        let filter = JobFilter {
+            bounty_min: Some(100),
+            bounty_max: Some(200),
+            ..Default::default()
+        };
+        assert_eq!(ids(filter), Some(vec![1, 2]));
+
+        let filter = JobFilter {
+            created_from: Some(20),
+            ..Default::default()
+        };
+        assert_eq!(ids(filter), Some(vec![2, 3]));
+    }
+
+    #[test]
+    fn skills_match_any_or_all() {
+        sample();
+
+        let any = JobFilter {
+            skill_ids: Some(vec![1, 2]),
+            ..Default::default()
+        };
+        assert_eq!(ids(any), Some(vec![1, 2, 3]));
+
+        let all = JobFilter {
+            skill_ids: Some(vec![1, 2]),
+            skill_match: Some(SkillMatch::All),
+            ..Default::default()
+        };
+        assert_eq!(ids(all), Some(vec![1]));
+    }
+
+    #[test]
+    fn a_skill_matches_the_skills_below_it() {
+        sample();
+        skills::SKILL_PARENT_STORE.with(|store| store.borrow_mut().insert(2, 5));
+        skills::rebuild();
+
+        let filter = JobFilter {
+            skill_ids: Some(vec![5]),
+            ..Default::default()
+        };
+        assert_eq!(ids(filter), Some(vec![1, 3]));
+    }
+
+    #[test]
+    fn inverted_ranges_are_rejected() {
+        let filter = JobFilter {
+            bounty_min: Some(2),
+            bounty_max: Some(1),
+            ..Default::default()
+        };
+        assert!(candidates(&filter).is_err());
+
+        let filter = JobFilter {
+            created_from: Some(2),
+            created_to: Some(1),
+            ..Default::default()
+        };
+        assert!(candidates(&filter).is_err());
+    }
+
+    #[test]
+    fn facets_count_only_the_candidates() {
+        sample();
+
+        let candidates = BTreeSet::from([1, 3]);
+        let facets = facets(Some(&candidates));
+
+        let statuses: BTreeMap<JobStatus, u64> = facets
+            .statuses
+            .into_iter()
+            .map(|count| (count.status, count.count))
+            .collect();
+        assert_eq!(statuses, BTreeMap::from([(JobStatus::Open, 2)]));
+
+        let skills: BTreeMap<u16, u64> = facets
+            .skills
+            .into_iter()
+            .map(|count| (count.skill_id, count.count))
+            .collect();
+        assert_eq!(skills, BTreeMap::from([(1, 1), (2, 2)]));
+
+        assert_eq!(facets.companies.len(), 2);
+    }
+}
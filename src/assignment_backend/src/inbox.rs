@@ -0,0 +1,121 @@
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::time;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+
+use crate::teams;
+
+// older notifications are dropped once an inbox holds this many
+pub const MAX_NOTIFICATIONS: usize = 100;
+
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub enum NotificationKind {
+    // a job matching one of the recipient's saved searches was posted
+    JobAlert { search_id: u64, job_id: u64 },
+
+    // sent to every member of the company
+    ApplicationReceived { application_id: u64, job_id: u64 },
+    OfferAccepted { application_id: u64, job_id: u64 },
+    OfferDeclined { application_id: u64, job_id: u64 },
+    ApplicationWithdrawn { application_id: u64, job_id: u64 },
+
+    // sent to the applicant
+    OfferMade { application_id: u64, job_id: u64 },
+    ApplicationRejected { application_id: u64, job_id: u64 },
+    JobCanceled { job_id: u64 },
+    JobClosed { job_id: u64 },
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct Notification {
+    pub id: u64,
+    pub kind: NotificationKind,
+    pub read: bool,
+    pub created_at: u64,
+}
+
+// recipient -> notification id -> notification
+pub type InboxStore = BTreeMap<Principal, BTreeMap<u64, Notification>>;
+
+thread_local! {
+    pub(crate) static NOTIFICATION_ID_STORE: Cell<u64> = const { Cell::new(0) };
+    pub(crate) static INBOX_STORE: RefCell<InboxStore> = RefCell::default();
+}
+
+pub fn push(recipient: Principal, kind: NotificationKind) {
+    let id = NOTIFICATION_ID_STORE.with(|id| {
+        id.set(id.get() + 1);
+        id.get()
+    });
+
+    let notification = Notification {
+        id,
+        kind,
+        read: false,
+        created_at: time(),
+    };
+
+    INBOX_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+        let inbox = store.entry(recipient).or_default();
+
+        inbox.insert(id, notification);
+        while inbox.len() > MAX_NOTIFICATIONS {
+            inbox.pop_first();
+        }
+    });
+}
+
+pub fn push_to_company(company_id: &Principal, kind: NotificationKind) {
+    for member in teams::members_of(company_id) {
+        push(member.principal, kind.clone());
+    }
+}
+
+pub fn unread_count(recipient: &Principal) -> u64 {
+    INBOX_STORE.with(|store| {
+        store.borrow().get(recipient).map_or(0, |inbox| {
+            inbox
+                .values()
+                .filter(|notification| !notification.read)
+                .count() as u64
+        })
+    })
+}
+
+pub fn mark_all_read(recipient: &Principal) -> u64 {
+    INBOX_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+        let Some(inbox) = store.get_mut(recipient) else {
+            return 0;
+        };
+
+        let mut marked = 0;
+        for notification in inbox.values_mut().filter(|n| !n.read) {
+            notification.read = true;
+            marked += 1;
+        }
+
+        marked
+    })
+}
+
+// ids that are not in the recipient's inbox are skipped, returns how many were marked
+pub fn mark_read(recipient: &Principal, ids: &[u64]) -> u64 {
+    INBOX_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+        let Some(inbox) = store.get_mut(recipient) else {
+            return 0;
+        };
+
+        let mut marked = 0;
+        for id in ids {
+            if let Some(notification) = inbox.get_mut(id).filter(|n| !n.read) {
+                notification.read = true;
+                marked += 1;
+            }
+        }
+
+        marked
+    })
+}
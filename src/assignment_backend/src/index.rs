@@ -0,0 +1,405 @@
+use candid::Principal;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::RangeInclusive;
+
+use crate::audit::{self, AuditAction, AuditEntity, AuditStatus};
+use crate::search;
+use crate::{ApplicantProfile, Application, ApplicationStatus, Job, JobStatus};
+use crate::{APPLICANT_PROFILE_STORE, APPLICATION_STORE, JOB_STORE};
+
+// secondary indexes, key -> ids of the records holding that key.
+// they are derived from the stores, so they are not persisted but rebuilt after an upgrade.
+type Index<K, V = u64> = BTreeMap<K, BTreeSet<V>>;
+
+thread_local! {
+    static JOB_BY_COMPANY_STORE: RefCell<Index<Principal>> = RefCell::default();
+    static JOB_BY_STATUS_STORE: RefCell<Index<JobStatus>> = RefCell::default();
+    static JOB_BY_SKILL_STORE: RefCell<Index<u16>> = RefCell::default();
+    static JOB_BY_BOUNTY_STORE: RefCell<Index<u64>> = RefCell::default();
+    static JOB_BY_CREATED_STORE: RefCell<Index<u64>> = RefCell::default();
+    // open jobs only, the expiry timer waits for the first key
+    static JOB_BY_EXPIRY_STORE: RefCell<Index<u64>> = RefCell::default();
+
+    static APPLICATION_BY_JOB_STORE: RefCell<Index<u64>> = RefCell::default();
+    static APPLICATION_BY_APPLICANT_STORE: RefCell<Index<Principal>> = RefCell::default();
+    static APPLICATION_BY_STATUS_STORE: RefCell<Index<ApplicationStatus>> = RefCell::default();
+
+    static APPLICANT_BY_SKILL_STORE: RefCell<Index<u16, Principal>> = RefCell::default();
+}
+
+fn add<K: Ord, V: Ord>(index: &mut Index<K, V>, key: K, id: V) {
+    index.entry(key).or_default().insert(id);
+}
+
+fn remove<K: Ord, V: Ord>(index: &mut Index<K, V>, key: &K, id: V) {
+    if let Some(ids) = index.get_mut(key) {
+        ids.remove(&id);
+
+        if ids.is_empty() {
+            index.remove(key);
+        }
+    }
+}
+
+fn lookup<K: Ord, V: Ord + Clone>(index: &Index<K, V>, key: &K) -> BTreeSet<V> {
+    index.get(key).cloned().unwrap_or_default()
+}
+
+fn count<K: Ord, V: Ord>(index: &Index<K, V>, key: &K) -> u64 {
+    index.get(key).map_or(0, |ids| ids.len() as u64)
+}
+
+fn lookup_range<K: Ord, V: Ord + Clone>(
+    index: &Index<K, V>,
+    range: RangeInclusive<K>,
+) -> BTreeSet<V> {
+    index
+        .range(range)
+        .flat_map(|(_, ids)| ids.iter().cloned())
+        .collect()
+}
+
+// how many of `candidates` each key holds, every id counts when there are no candidates
+fn facet<K: Ord + Clone>(index: &Index<K>, candidates: Option<&BTreeSet<u64>>) -> BTreeMap<K, u64> {
+    index
+        .iter()
+        .map(|(key, ids)| {
+            let count = match candidates {
+                Some(candidates) => ids.intersection(candidates).count(),
+                None => ids.len(),
+            };
+            (key.clone(), count as u64)
+        })
+        .filter(|(_, count)| *count > 0)
+        .collect()
+}
+
+fn unindex_job(job: &Job) {
+    if let Some(company_id) = job.company_id {
+        JOB_BY_COMPANY_STORE.with(|index| remove(&mut index.borrow_mut(), &company_id, job.id));
+    }
+
+    JOB_BY_STATUS_STORE.with(|index| remove(&mut index.borrow_mut(), &job.status, job.id));
+    JOB_BY_BOUNTY_STORE.with(|index| remove(&mut index.borrow_mut(), &job.bounty, job.id));
+    JOB_BY_CREATED_STORE.with(|index| remove(&mut index.borrow_mut(), &job.created_at, job.id));
+
+    if let Some(expires_at) = job.expires_at.filter(|_| job.status == JobStatus::Open) {
+        JOB_BY_EXPIRY_STORE.with(|index| remove(&mut index.borrow_mut(), &expires_at, job.id));
+    }
+
+    JOB_BY_SKILL_STORE.with(|index| {
+        for skill_id in job.required_skills.keys() {
+            remove(&mut index.borrow_mut(), skill_id, job.id);
+        }
+    });
+}
+
+fn index_job(job: &Job) {
+    if let Some(company_id) = job.company_id {
+        JOB_BY_COMPANY_STORE.with(|index| add(&mut index.borrow_mut(), company_id, job.id));
+    }
+
+    JOB_BY_STATUS_STORE.with(|index| add(&mut index.borrow_mut(), job.status.clone(), job.id));
+    JOB_BY_BOUNTY_STORE.with(|index| add(&mut index.borrow_mut(), job.bounty, job.id));
+    JOB_BY_CREATED_STORE.with(|index| add(&mut index.borrow_mut(), job.created_at, job.id));
+
+    if let Some(expires_at) = job.expires_at.filter(|_| job.status == JobStatus::Open) {
+        JOB_BY_EXPIRY_STORE.with(|index| add(&mut index.borrow_mut(), expires_at, job.id));
+    }
+
+    JOB_BY_SKILL_STORE.with(|index| {
+        for skill_id in job.required_skills.keys() {
+            add(&mut index.borrow_mut(), *skill_id, job.id);
+        }
+    });
+}
+
+fn unindex_application(application: &Application) {
+    APPLICATION_BY_JOB_STORE
+        .with(|index| remove(&mut index.borrow_mut(), &application.job_id, application.id));
+
+    if let Some(applicant_id) = application.applicant_id {
+        APPLICATION_BY_APPLICANT_STORE
+            .with(|index| remove(&mut index.borrow_mut(), &applicant_id, application.id));
+    }
+
+    APPLICATION_BY_STATUS_STORE
+        .with(|index| remove(&mut index.borrow_mut(), &application.status, application.id));
+}
+
+fn index_application(application: &Application) {
+    APPLICATION_BY_JOB_STORE
+        .with(|index| add(&mut index.borrow_mut(), application.job_id, application.id));
+
+    if let Some(applicant_id) = application.applicant_id {
+        APPLICATION_BY_APPLICANT_STORE
+            .with(|index| add(&mut index.borrow_mut(), applicant_id, application.id));
+    }
+
+    APPLICATION_BY_STATUS_STORE.with(|index| {
+        add(
+            &mut index.borrow_mut(),
+            application.status.clone(),
+            application.id,
+        )
+    });
+}
+
+fn unindex_applicant(principal: Principal, profile: &ApplicantProfile) {
+    APPLICANT_BY_SKILL_STORE.with(|index| {
+        for skill_id in profile.skills.keys() {
+            remove(&mut index.borrow_mut(), skill_id, principal);
+        }
+    });
+}
+
+fn index_applicant(principal: Principal, profile: &ApplicantProfile) {
+    APPLICANT_BY_SKILL_STORE.with(|index| {
+        for skill_id in profile.skills.keys() {
+            add(&mut index.borrow_mut(), *skill_id, principal);
+        }
+    });
+}
+
+// every write to JOB_STORE goes through here, so the indexes never drift from the store
+pub fn insert_job(job: Job) {
+    let previous = JOB_STORE.with(|store| store.borrow_mut().insert(job.id, job.clone()));
+
+    if let Some(previous) = &previous {
+        unindex_job(previous);
+    }
+
+    index_job(&job);
+    search::reindex(previous.as_ref(), &job);
+    audit::record(
+        AuditEntity::Job(job.id),
+        previous.map(|previous| AuditStatus::Job(previous.status)),
+        AuditStatus::Job(job.status),
+    );
+}
+
+// every write to APPLICATION_STORE goes through here, so the indexes never drift from the store
+pub fn insert_application(application: Application) {
+    let previous = APPLICATION_STORE.with(|store| {
+        store
+            .borrow_mut()
+            .insert(application.id, application.clone())
+    });
+
+    if let Some(previous) = &previous {
+        unindex_application(previous);
+    }
+
+    index_application(&application);
+    audit::record(
+        AuditEntity::Application(application.id),
+        previous.map(|previous| AuditStatus::Application(previous.status)),
+        AuditStatus::Application(application.status),
+    );
+}
+
+// every write to APPLICANT_PROFILE_STORE goes through here, so the indexes never drift from the store
+pub fn insert_applicant(principal: Principal, profile: ApplicantProfile) {
+    let previous =
+        APPLICANT_PROFILE_STORE.with(|store| store.borrow_mut().insert(principal, profile.clone()));
+
+    if let Some(previous) = &previous {
+        unindex_applicant(principal, previous);
+    }
+
+    index_applicant(principal, &profile);
+    let action = if previous.is_none() {
+        AuditAction::Created
+    } else {
+        AuditAction::Updated
+    };
+    audit::record_change(AuditEntity::ApplicantProfile(principal), action);
+}
+
+pub fn rebuild() {
+    JOB_BY_COMPANY_STORE.with(|index| index.borrow_mut().clear());
+    JOB_BY_STATUS_STORE.with(|index| index.borrow_mut().clear());
+    JOB_BY_SKILL_STORE.with(|index| index.borrow_mut().clear());
+    JOB_BY_BOUNTY_STORE.with(|index| index.borrow_mut().clear());
+    JOB_BY_CREATED_STORE.with(|index| index.borrow_mut().clear());
+    JOB_BY_EXPIRY_STORE.with(|index| index.borrow_mut().clear());
+    APPLICATION_BY_JOB_STORE.with(|index| index.borrow_mut().clear());
+    APPLICATION_BY_APPLICANT_STORE.with(|index| index.borrow_mut().clear());
+    APPLICATION_BY_STATUS_STORE.with(|index| index.borrow_mut().clear());
+    APPLICANT_BY_SKILL_STORE.with(|index| index.borrow_mut().clear());
+
+    APPLICANT_PROFILE_STORE.with(|store| {
+        store
+            .borrow()
+            .iter()
+            .for_each(|(principal, profile)| index_applicant(*principal, profile))
+    });
+    JOB_STORE.with(|store| store.borrow().values().for_each(index_job));
+    APPLICATION_STORE.with(|store| store.borrow().values().for_each(index_application));
+}
+
+pub fn jobs_by_company(company_id: &Principal) -> BTreeSet<u64> {
+    JOB_BY_COMPANY_STORE.with(|index| lookup(&index.borrow(), company_id))
+}
+
+pub fn jobs_by_status(status: &JobStatus) -> BTreeSet<u64> {
+    JOB_BY_STATUS_STORE.with(|index| lookup(&index.borrow(), status))
+}
+
+pub fn jobs_by_skill(skill_id: &u16) -> BTreeSet<u64> {
+    JOB_BY_SKILL_STORE.with(|index| lookup(&index.borrow(), skill_id))
+}
+
+pub fn jobs_by_bounty(range: RangeInclusive<u64>) -> BTreeSet<u64> {
+    JOB_BY_BOUNTY_STORE.with(|index| lookup_range(&index.borrow(), range))
+}
+
+pub fn jobs_created_between(range: RangeInclusive<u64>) -> BTreeSet<u64> {
+    JOB_BY_CREATED_STORE.with(|index| lookup_range(&index.borrow(), range))
+}
+
+// open jobs that expire by `time`
+pub fn jobs_expiring_by(time: u64) -> BTreeSet<u64> {
+    JOB_BY_EXPIRY_STORE.with(|index| lookup_range(&index.borrow(), 0..=time))
+}
+
+pub fn next_expiry() -> Option<u64> {
+    JOB_BY_EXPIRY_STORE.with(|index| index.borrow().keys().next().cloned())
+}
+
+pub fn job_status_facets(candidates: Option<&BTreeSet<u64>>) -> BTreeMap<JobStatus, u64> {
+    JOB_BY_STATUS_STORE.with(|index| facet(&index.borrow(), candidates))
+}
+
+pub fn job_skill_facets(candidates: Option<&BTreeSet<u64>>) -> BTreeMap<u16, u64> {
+    JOB_BY_SKILL_STORE.with(|index| facet(&index.borrow(), candidates))
+}
+
+pub fn job_company_facets(candidates: Option<&BTreeSet<u64>>) -> BTreeMap<Principal, u64> {
+    JOB_BY_COMPANY_STORE.with(|index| facet(&index.borrow(), candidates))
+}
+
+pub fn applications_by_job(job_id: &u64) -> BTreeSet<u64> {
+    APPLICATION_BY_JOB_STORE.with(|index| lookup(&index.borrow(), job_id))
+}
+
+pub fn applications_by_applicant(applicant_id: &Principal) -> BTreeSet<u64> {
+    APPLICATION_BY_APPLICANT_STORE.with(|index| lookup(&index.borrow(), applicant_id))
+}
+
+pub fn applications_by_status(status: &ApplicationStatus) -> BTreeSet<u64> {
+    APPLICATION_BY_STATUS_STORE.with(|index| lookup(&index.borrow(), status))
+}
+
+pub fn applicants_by_skill(skill_id: &u16) -> BTreeSet<Principal> {
+    APPLICANT_BY_SKILL_STORE.with(|index| lookup(&index.borrow(), skill_id))
+}
+
+pub fn job_count_by_skill(skill_id: &u16) -> u64 {
+    JOB_BY_SKILL_STORE.with(|index| count(&index.borrow(), skill_id))
+}
+
+pub fn applicant_count_by_skill(skill_id: &u16) -> u64 {
+    APPLICANT_BY_SKILL_STORE.with(|index| count(&index.borrow(), skill_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn principal(byte: u8) -> Principal {
+        Principal::from_slice(&[byte])
+    }
+
+    fn job(id: u64, company: u8, status: JobStatus) -> Job {
+        Job {
+            id,
+            company_id: Some(principal(company)),
+            status,
+            required_skills: BTreeMap::from([(7, Default::default())]),
+            ..Default::default()
+        }
+    }
+
+    fn application(id: u64, job_id: u64, applicant: u8, status: ApplicationStatus) -> Application {
+        Application {
+            id,
+            job_id,
+            applicant_id: Some(principal(applicant)),
+            status,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn removing_the_last_id_drops_the_key() {
+        let mut index = Index::<u16>::new();
+        add(&mut index, 1, 10);
+        add(&mut index, 1, 11);
+
+        remove(&mut index, &1, 10);
+        assert_eq!(lookup(&index, &1), BTreeSet::from([11]));
+
+        remove(&mut index, &1, 11);
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn rebuild_indexes_every_stored_job() {
+        JOB_STORE.with(|store| {
+            let mut store = store.borrow_mut();
+            store.insert(1, job(1, 1, JobStatus::Open));
+            store.insert(2, job(2, 1, JobStatus::Closed));
+            store.insert(3, job(3, 2, JobStatus::Open));
+        });
+
+        rebuild();
+
+        assert_eq!(jobs_by_company(&principal(1)), BTreeSet::from([1, 2]));
+        assert_eq!(jobs_by_status(&JobStatus::Open), BTreeSet::from([1, 3]));
+        assert_eq!(jobs_by_skill(&7), BTreeSet::from([1, 2, 3]));
+        assert!(jobs_by_skill(&8).is_empty());
+    }
+
+    #[test]
+    fn a_status_change_moves_the_job() {
+        let before = job(1, 1, JobStatus::Open);
+        let after = Job {
+            status: JobStatus::Closed,
+            ..before.clone()
+        };
+
+        index_job(&before);
+        unindex_job(&before);
+        index_job(&after);
+
+        assert!(jobs_by_status(&JobStatus::Open).is_empty());
+        assert_eq!(jobs_by_status(&JobStatus::Closed), BTreeSet::from([1]));
+        assert_eq!(jobs_by_company(&principal(1)), BTreeSet::from([1]));
+    }
+
+    #[test]
+    fn rebuild_indexes_every_stored_application() {
+        APPLICATION_STORE.with(|store| {
+            let mut store = store.borrow_mut();
+            store.insert(1, application(1, 1, 3, ApplicationStatus::Applied));
+            store.insert(2, application(2, 1, 4, ApplicationStatus::Offer));
+            store.insert(3, application(3, 2, 3, ApplicationStatus::Applied));
+        });
+
+        rebuild();
+
+        assert_eq!(applications_by_job(&1), BTreeSet::from([1, 2]));
+        assert_eq!(
+            applications_by_applicant(&principal(3)),
+            BTreeSet::from([1, 3])
+        );
+        assert_eq!(
+            applications_by_status(&ApplicationStatus::Applied),
+            BTreeSet::from([1, 3])
+        );
+    }
+}
@@ -1,16 +1,74 @@
 use candid::Principal;
+use ic_cdk::api::management_canister::http_request::{HttpResponse, TransformArgs};
 use ic_cdk::api::time;
-use ic_cdk::{query, update};
+use ic_cdk::{init, post_upgrade, pre_upgrade, query, update};
 use std::cell::{Cell, RefCell};
-use std::collections::BTreeMap;
-use std::ops::Bound::Included;
+use std::collections::{BTreeMap, BTreeSet};
 
 pub mod state;
 pub use state::*;
 
+pub mod error;
+pub use error::*;
+
+pub mod pagination;
+pub use pagination::*;
+
+pub mod roles;
+pub use roles::{AdminAction, AdminLogEntry, InitArgs, Role};
+
+pub mod teams;
+pub use teams::{CompanyInvite, CompanyMember, MemberRole};
+
+pub mod escrow;
+pub use escrow::{Escrow, EscrowStatus, LedgerConfig, Payout};
+
+pub mod skills;
+pub use skills::{SkillNode, SkillUsage};
+
+pub mod endorsements;
+pub use endorsements::{Endorsement, EndorsementKind, SkillWeight};
+
+pub mod talent;
+pub use talent::{
+    ContactRequest, ContactStatus, TalentHit, TalentParams, TalentQuery, TalentSettings,
+};
+
+pub mod recommend;
+pub use recommend::{CandidateRecommendation, JobRecommendation, MatchBreakdown};
+
+pub mod inbox;
+pub use inbox::{Notification, NotificationKind};
+
+pub mod alerts;
+pub use alerts::{SavedSearch, SavedSearchParams};
+
+pub mod filter;
+pub use filter::{FilteredJobs, JobFacets, JobFilter, SkillMatch};
+
+pub mod search;
+pub use search::SearchHit;
+
+pub mod audit;
+pub use audit::{AuditAction, AuditEntity, AuditEvent, AuditFilter, AuditStatus};
+
+pub mod events;
+pub use events::{BoardEvent, EventBatch};
+
+pub mod webhooks;
+pub use webhooks::{Delivery, DeliveryStatus, WebhookParams, WebhookResponse};
+
+pub mod expiry;
+
+mod index;
+mod migration;
+mod storage;
+mod transition;
+
+use transition::Actor;
+
 type ApplicantProfileStore = BTreeMap<Principal, ApplicantProfile>;
 type CompanyProfileStore = BTreeMap<Principal, CompanyProfile>;
-type JobByCompanyStore = BTreeMap<Principal, Vec<u64>>;
 
 type JobStore = BTreeMap<u64, Job>;
 type ApplicationStore = BTreeMap<u64, Application>;
@@ -18,392 +76,1215 @@ type SkillStore = BTreeMap<u16, Skill>;
 
 thread_local! {
 
-    static APPLICANT_PROFILE_STORE: RefCell<ApplicantProfileStore> = RefCell::default();
-    static COMPANY_PROFILE_STORE: RefCell<CompanyProfileStore> = RefCell::default();
+    static APPLICANT_PROFILE_STORE: RefCell<ApplicantProfileStore> = RefCell::default();
+    static COMPANY_PROFILE_STORE: RefCell<CompanyProfileStore> = RefCell::default();
+
+    static JOB_ID_STORE: Cell<u64> = const { Cell::new(0) };
+    static JOB_STORE: RefCell<JobStore> = RefCell::default();
+
+    static APPLICATION_ID_STORE: Cell<u64> = const { Cell::new(0) };
+    static APPLICATION_STORE: RefCell<ApplicationStore> = RefCell::default();
+
+    static SKILL_ID_STORE: Cell<u16> = const { Cell::new(0) };
+    static SKILL_STORE: RefCell<SkillStore> = RefCell::default();
+}
+
+#[init]
+fn init(args: Option<InitArgs>) {
+    let args = args.unwrap_or_default();
+
+    roles::seed(&args.admins);
+    if let Some(ledger) = args.ledger {
+        escrow::configure(ledger);
+    }
+    if let Some(status) = args.expired_application_status {
+        expiry::configure(status);
+    }
+}
+
+#[pre_upgrade]
+fn pre_upgrade() {
+    storage::save();
+}
+
+// upgrade args are optional, admins listed there are added to the existing ones
+// and a ledger listed there replaces the configured one
+#[post_upgrade]
+fn post_upgrade(args: Option<InitArgs>) {
+    let args = args.unwrap_or_default();
+
+    storage::load();
+    index::rebuild();
+    teams::rebuild();
+    skills::rebuild();
+    endorsements::rebuild();
+    talent::rebuild();
+    alerts::rebuild();
+    search::rebuild();
+    events::certify();
+    alerts::schedule();
+    webhooks::schedule();
+    expiry::schedule();
+    roles::seed(&args.admins);
+    if let Some(ledger) = args.ledger {
+        escrow::configure(ledger);
+    }
+    if let Some(status) = args.expired_application_status {
+        expiry::configure(status);
+    }
+}
+
+fn is_controller() -> BoardResult<()> {
+    if !ic_cdk::api::is_controller(&ic_cdk::api::caller()) {
+        return Err(BoardError::unauthorized("Caller is not a controller"));
+    };
+
+    Ok(())
+}
+
+// the live state is exported by taking a snapshot, then reading it back a chunk at a time
+#[update]
+fn start_state_export() -> BoardResult<StateExport> {
+    is_controller()?;
+
+    Ok(storage::start_export())
+}
+
+#[query]
+fn export_state_chunk(offset: u64) -> BoardResult<Vec<u8>> {
+    is_controller()?;
+
+    storage::export_chunk(offset)
+}
+
+// an exported snapshot is uploaded in the same chunks, then checked with dry_run_migration
+#[update]
+fn start_snapshot_upload(version: u32) -> BoardResult<()> {
+    is_controller()?;
+
+    storage::start_upload(version);
+
+    Ok(())
+}
+
+#[update]
+fn upload_snapshot_chunk(data: Vec<u8>) -> BoardResult<u64> {
+    is_controller()?;
+
+    storage::upload_chunk(data)
+}
+
+#[query]
+fn dry_run_migration() -> BoardResult<MigrationReport> {
+    is_controller()?;
+
+    migration::dry_run(storage::uploaded()?)
+        .map_err(|reason| BoardError::validation("snapshot", &reason))
+}
+
+fn is_admin() -> BoardResult<()> {
+    if !roles::is_admin(&ic_cdk::api::caller()) {
+        return Err(BoardError::unauthorized("User is not an Admin"));
+    };
+
+    Ok(())
+}
+
+fn is_moderator() -> BoardResult<()> {
+    if !roles::is_moderator(&ic_cdk::api::caller()) {
+        return Err(BoardError::unauthorized("User is not a Moderator"));
+    };
+
+    Ok(())
+}
+
+fn is_valid_create_user() -> BoardResult<()> {
+    let principal_id = ic_cdk::api::caller();
+
+    if roles::has_role(&principal_id, Role::Company) {
+        return Err(BoardError::conflict("User already exist as Company"));
+    };
+
+    if roles::has_role(&principal_id, Role::Applicant) {
+        return Err(BoardError::conflict("User already exist as Applicant"));
+    };
+
+    Ok(())
+}
+
+fn is_valid_applicant() -> BoardResult<()> {
+    if !roles::has_role(&ic_cdk::api::caller(), Role::Applicant) {
+        return Err(BoardError::unauthorized("User is not an Applicant"));
+    };
+
+    Ok(())
+}
+
+#[update]
+fn create_applicant_profile(
+    params: ApplicantParams,
+    skills: Vec<ApplicantSkill>,
+) -> BoardResult<ApplicantProfileResponse> {
+    is_valid_create_user()?;
+    let principal_id = ic_cdk::api::caller();
+
+    let profile = ApplicantProfile {
+        id: Some(principal_id),
+        first_name: params.first_name.to_lowercase(),
+        last_name: params.last_name.to_lowercase(),
+        nickname: params.nickname.to_lowercase(),
+        bio: params.bio.to_lowercase(),
+
+        // blocktime() -> is this a thing? will come back to this later
+        created_at: time(),
+        updated_at: time(),
+        skills: skills::resolve(skills)?,
+    };
+
+    index::insert_applicant(principal_id, profile.clone());
+    roles::grant(principal_id, Role::Applicant);
+
+    Ok(ApplicantProfileResponse::from(&profile))
+}
+
+#[update]
+fn create_company_profile(params: CompanyParams) -> BoardResult<CompanyProfile> {
+    is_valid_create_user()?;
+    let principal_id = ic_cdk::api::caller();
+
+    // a principal that lost its Company role still owns whatever it founded
+    let founded = COMPANY_PROFILE_STORE.with(|store| store.borrow().contains_key(&principal_id));
+    if founded || teams::exists(&principal_id) {
+        return Err(BoardError::conflict("User already founded a Company"));
+    }
+
+    let profile = CompanyProfile {
+        id: Some(principal_id),
+        name: params.name.to_lowercase(),
+        logo: params.logo.to_lowercase(),
+        twitter: params.twitter.to_lowercase(),
+        website: params.website.to_lowercase(),
+
+        // blocktime() -> is this a thing? will come back to this later
+        created_at: time(),
+        updated_at: time(),
+    };
+
+    COMPANY_PROFILE_STORE.with(|profile_store| {
+        profile_store
+            .borrow_mut()
+            .insert(principal_id, profile.clone());
+    });
+    teams::create(principal_id);
+    audit::record_change(
+        AuditEntity::CompanyProfile(principal_id),
+        AuditAction::Created,
+    );
+
+    Ok(profile)
+}
+
+#[update]
+async fn create_job(params: JobParams, skills: Vec<JobSkill>) -> BoardResult<u64> {
+    let principal_id = ic_cdk::api::caller();
+    let company_id = teams::authorize(&principal_id, MemberRole::Recruiter)?;
+
+    if let Some(expires_at) = params.expires_at {
+        expiry::validate(expires_at)?;
+    }
+
+    // skills are resolved before the ledger is called, so a bad skill costs the caller nothing.
+    // they are held meanwhile, a sweep would remove the new ones before the job holds them.
+    let required_skills = skills::resolve(skills)?;
+    let held: Vec<u16> = required_skills.keys().cloned().collect();
+    skills::hold(held.iter().cloned());
+
+    // the bounty is paid by the member posting the job
+    let funded = escrow::fund_new(principal_id, params.bounty).await;
+    skills::release(held);
+    let escrow = funded?;
+
+    // the job only takes an id once its bounty is in, a failed funding leaves no gap
+    let id = JOB_ID_STORE.with(|id| {
+        id.set(id.get() + 1);
+        id.get()
+    });
+    if let Some(escrow) = escrow {
+        escrow::hold(id, escrow);
+    }
+
+    let required_skills = skills::refresh(required_skills);
+
+    let job = Job {
+        id,
+        company_id: Some(company_id),
+        position: params.position.to_lowercase(),
+        description: params.description.to_lowercase(),
+        bounty: params.bounty,
+        status: JobStatus::Open,
+        created_at: time(),
+        updated_at: time(),
+        expires_at: params.expires_at,
+        expired_at: None,
+        required_skills,
+    };
+
+    index::insert_job(job.clone());
+    alerts::notify(&job);
+    expiry::schedule();
+
+    Ok(id)
+}
+
+#[update]
+fn apply_to_job(params: ApplicationParams) -> BoardResult<u64> {
+    is_valid_applicant()?;
+    let principal_id = ic_cdk::api::caller();
+
+    // VALIDATIONS
+    let job = JOB_STORE
+        .with(|job_store| job_store.borrow().get(&params.job_id).cloned())
+        .ok_or_else(|| BoardError::not_found("job", params.job_id))?;
+
+    if job.status != JobStatus::Open {
+        return Err(BoardError::conflict("Job is not open for applications"));
+    }
+
+    let already_applied = index::applications_by_applicant(&principal_id)
+        .intersection(&index::applications_by_job(&params.job_id))
+        .next()
+        .is_some();
+
+    if already_applied {
+        return Err(BoardError::conflict("Already applied to this job"));
+    }
+
+    validate_salary(params.salary_from, params.salary_to)?;
+
+    let id = APPLICATION_ID_STORE.with(|id| {
+        id.set(id.get() + 1);
+        id.get()
+    });
+
+    index::insert_application(Application {
+        id,
+        applicant_id: Some(principal_id),
+        job_id: params.job_id,
+        status: ApplicationStatus::Applied,
+        contact_email: params.contact_email.to_lowercase(),
+        salary_from: params.salary_from,
+        salary_to: params.salary_to,
+        updated_at: time(),
+        referrer: params.referrer,
+    });
+
+    if let Some(company_id) = job.company_id {
+        let kind = NotificationKind::ApplicationReceived {
+            application_id: id,
+            job_id: job.id,
+        };
+
+        webhooks::enqueue(company_id, &kind);
+        inbox::push_to_company(&company_id, kind);
+    }
+
+    Ok(id)
+}
+
+fn validate_salary(salary_from: u64, salary_to: u64) -> BoardResult<()> {
+    if salary_from > salary_to {
+        return Err(BoardError::validation(
+            "salary_from",
+            "must not be greater than salary_to",
+        ));
+    }
+
+    Ok(())
+}
+
+// the job, if the caller is a member of the company that posted it with at least `required`
+fn get_owned_job(job_id: u64, principal_id: Principal, required: MemberRole) -> BoardResult<Job> {
+    let job = JOB_STORE
+        .with(|store| store.borrow().get(&job_id).cloned())
+        .ok_or_else(|| BoardError::not_found("job", job_id))?;
+
+    let company_id = job
+        .company_id
+        .ok_or_else(|| BoardError::unauthorized("Job belongs to another company"))?;
+    teams::authorize_for(&company_id, &principal_id, required)?;
+
+    Ok(job)
+}
+
+fn get_application_record(id: u64) -> BoardResult<Application> {
+    APPLICATION_STORE
+        .with(|store| store.borrow().get(&id).cloned())
+        .ok_or_else(|| BoardError::not_found("application", id))
+}
+
+#[update]
+fn make_offer(appliation_id: u64, job_id: u64, accept: bool) -> BoardResult<Application> {
+    let principal_id = ic_cdk::api::caller();
+
+    get_owned_job(job_id, principal_id, MemberRole::Recruiter)?;
+    let application = get_application_record(appliation_id)?;
+
+    if application.job_id != job_id {
+        return Err(BoardError::validation(
+            "job_id",
+            "application was not made to this job",
+        ));
+    }
+
+    let status = if accept {
+        ApplicationStatus::Offer
+    } else {
+        ApplicationStatus::Rejected
+    };
+
+    transition::application(application, status, Actor::Company)
+}
+
+// #[update]
+// fn make_offer_directly(applicant_id: Principal, job_id: u64, params: ApplicationParams) {}
+
+#[update(name = "accept_offer")]
+fn accpet_offer(id: u64, accept: bool) -> BoardResult<Application> {
+    let principal_id = ic_cdk::api::caller();
+    let application = get_application_record(id)?;
+
+    if application.applicant_id != Some(principal_id) {
+        return Err(BoardError::unauthorized(
+            "Application belongs to another applicant",
+        ));
+    }
+
+    let status = if accept {
+        ApplicationStatus::Accepted
+    } else {
+        ApplicationStatus::Rejected
+    };
+
+    transition::application(application, status, Actor::Applicant)
+}
+
+#[update]
+fn cancel_job(id: u64) -> BoardResult<JobResponse> {
+    let principal_id = ic_cdk::api::caller();
+    let job = get_owned_job(id, principal_id, MemberRole::Recruiter)?;
+    let job = transition::job(job, JobStatus::Canceled, Actor::Company)?;
+
+    Ok(JobResponse::from(&job))
+}
+
+#[update]
+fn close_job(id: u64) -> BoardResult<JobResponse> {
+    let principal_id = ic_cdk::api::caller();
+    let job = get_owned_job(id, principal_id, MemberRole::Recruiter)?;
+    let job = transition::job(job, JobStatus::Closed, Actor::Company)?;
+
+    Ok(JobResponse::from(&job))
+}
+
+// moves when an open job expires, `null` keeps it open until it is closed
+#[update]
+fn extend_job(id: u64, expires_at: Option<u64>) -> BoardResult<JobResponse> {
+    let principal_id = ic_cdk::api::caller();
+    let job = get_owned_job(id, principal_id, MemberRole::Recruiter)?;
+    let job = expiry::extend(job, expires_at)?;
+
+    Ok(JobResponse::from(&job))
+}
+
+// opens an expired job again. its bounty was refunded when it expired,
+// so the member reopening it escrows the bounty again, like when posting.
+#[update]
+async fn reopen_job(id: u64, expires_at: Option<u64>) -> BoardResult<JobResponse> {
+    let principal_id = ic_cdk::api::caller();
+    let job = get_owned_job(id, principal_id, MemberRole::Recruiter)?;
+
+    if let Some(expires_at) = expires_at {
+        expiry::validate(expires_at)?;
+    }
+
+    let refunding =
+        escrow::get(id).is_some_and(|escrow| !matches!(escrow.status, EscrowStatus::Paid { .. }));
+    if refunding {
+        return Err(BoardError::conflict(
+            "The bounty of this job is still being refunded",
+        ));
+    }
+
+    expiry::start_reopen(&job)?;
+    let funded = escrow::fund(id, principal_id, job.bounty).await;
+
+    // the job may have changed while the ledger was called
+    let result = funded.and_then(|_| {
+        let job = get_owned_job(id, principal_id, MemberRole::Recruiter)?;
+        transition::job(Job { expires_at, ..job }, JobStatus::Open, Actor::Company)
+    });
+
+    if result.is_err() {
+        escrow::refund(id);
+    }
+    expiry::finish_reopen(id);
+
+    result.map(|job| JobResponse::from(&job))
+}
+
+#[update]
+fn withdraw_application(id: u64) -> BoardResult<Application> {
+    let principal_id = ic_cdk::api::caller();
+    let application = get_application_record(id)?;
+
+    if application.applicant_id != Some(principal_id) {
+        return Err(BoardError::unauthorized(
+            "Application belongs to another applicant",
+        ));
+    }
+
+    transition::application(application, ApplicationStatus::Withdraw, Actor::Applicant)
+}
+
+// UPDATES
+//  partial updates, fields left empty keep their current value.
+
+fn edit_job(job: Job, params: UpdateJobParams) -> BoardResult<JobResponse> {
+    if job.status != JobStatus::Open {
+        return Err(BoardError::conflict("Only open jobs can be edited"));
+    }
+
+    if params.bounty.is_some_and(|bounty| bounty != job.bounty) {
+        return Err(BoardError::validation(
+            "bounty",
+            "is escrowed when the job is posted and can't be changed",
+        ));
+    }
+
+    let job = Job {
+        position: params
+            .position
+            .map(|value| value.to_lowercase())
+            .unwrap_or(job.position),
+        description: params
+            .description
+            .map(|value| value.to_lowercase())
+            .unwrap_or(job.description),
+        required_skills: params
+            .required_skills
+            .map(skills::resolve)
+            .transpose()?
+            .unwrap_or(job.required_skills),
+        updated_at: time(),
+        ..job
+    };
+
+    index::insert_job(job.clone());
+
+    Ok(JobResponse::from(&job))
+}
+
+fn edit_applicant_profile(
+    principal_id: Principal,
+    params: UpdateApplicantParams,
+) -> BoardResult<ApplicantProfileResponse> {
+    let skills = params.skills.map(skills::resolve).transpose()?;
+
+    let mut profile = APPLICANT_PROFILE_STORE
+        .with(|store| store.borrow().get(&principal_id).cloned())
+        .ok_or_else(|| BoardError::not_found("applicant", principal_id))?;
+
+    if let Some(first_name) = params.first_name {
+        profile.first_name = first_name.to_lowercase();
+    }
+
+    if let Some(last_name) = params.last_name {
+        profile.last_name = last_name.to_lowercase();
+    }
+
+    if let Some(nickname) = params.nickname {
+        profile.nickname = nickname.to_lowercase();
+    }
+
+    if let Some(bio) = params.bio {
+        profile.bio = bio.to_lowercase();
+    }
+
+    if let Some(skills) = skills {
+        profile.skills = skills;
+    }
+
+    profile.updated_at = time();
+    index::insert_applicant(principal_id, profile.clone());
+
+    Ok(ApplicantProfileResponse::from(&profile))
+}
+
+fn edit_company_profile(
+    principal_id: Principal,
+    params: UpdateCompanyParams,
+) -> BoardResult<CompanyProfile> {
+    COMPANY_PROFILE_STORE.with(|profile_store| {
+        let mut profile_store = profile_store.borrow_mut();
+        let profile = profile_store
+            .get_mut(&principal_id)
+            .ok_or_else(|| BoardError::not_found("company", principal_id))?;
+
+        if let Some(name) = params.name {
+            profile.name = name.to_lowercase();
+        }
+
+        if let Some(logo) = params.logo {
+            profile.logo = logo.to_lowercase();
+        }
+
+        if let Some(twitter) = params.twitter {
+            profile.twitter = twitter.to_lowercase();
+        }
+
+        if let Some(website) = params.website {
+            profile.website = website.to_lowercase();
+        }
+
+        profile.updated_at = time();
+        audit::record_change(
+            AuditEntity::CompanyProfile(principal_id),
+            AuditAction::Updated,
+        );
+
+        Ok(profile.clone())
+    })
+}
+
+#[update]
+fn update_job(id: u64, params: UpdateJobParams) -> BoardResult<JobResponse> {
+    let principal_id = ic_cdk::api::caller();
+    let job = get_owned_job(id, principal_id, MemberRole::Recruiter)?;
+
+    edit_job(job, params)
+}
+
+#[update]
+fn update_application(id: u64, params: UpdateApplicationParams) -> BoardResult<Application> {
+    let principal_id = ic_cdk::api::caller();
+    let application = get_application_record(id)?;
+
+    if application.applicant_id != Some(principal_id) {
+        return Err(BoardError::unauthorized(
+            "Application belongs to another applicant",
+        ));
+    }
+
+    if application.status != ApplicationStatus::Applied {
+        return Err(BoardError::conflict(
+            "Only applications awaiting a decision can be edited",
+        ));
+    }
+
+    let application = Application {
+        contact_email: params
+            .contact_email
+            .map(|value| value.to_lowercase())
+            .unwrap_or(application.contact_email),
+        salary_from: params.salary_from.unwrap_or(application.salary_from),
+        salary_to: params.salary_to.unwrap_or(application.salary_to),
+        updated_at: time(),
+        ..application
+    };
+
+    validate_salary(application.salary_from, application.salary_to)?;
+
+    index::insert_application(application.clone());
+
+    Ok(application)
+}
+
+#[update]
+fn update_applicant_profile(
+    params: UpdateApplicantParams,
+) -> BoardResult<ApplicantProfileResponse> {
+    is_valid_applicant()?;
+
+    edit_applicant_profile(ic_cdk::api::caller(), params)
+}
+
+#[update]
+fn update_company_profile(params: UpdateCompanyParams) -> BoardResult<CompanyProfile> {
+    let company_id = teams::authorize(&ic_cdk::api::caller(), MemberRole::Owner)?;
+
+    edit_company_profile(company_id, params)
+}
+
+// TEAMS
+//  owners invite members, the invitee accepts, owners remove members or members leave.
+
+#[update]
+fn invite_member(principal: Principal, role: MemberRole) -> BoardResult<CompanyInvite> {
+    let principal_id = ic_cdk::api::caller();
+    let company_id = teams::authorize(&principal_id, MemberRole::Owner)?;
+
+    teams::invite(company_id, principal_id, principal, role)
+}
+
+#[update]
+fn accept_invite(company_id: Principal) -> BoardResult<CompanyMember> {
+    teams::accept(ic_cdk::api::caller(), company_id)
+}
+
+#[update]
+fn remove_member(principal: Principal) -> BoardResult<Vec<CompanyMember>> {
+    let principal_id = ic_cdk::api::caller();
+
+    // anyone may leave, removing someone else takes an owner
+    let company_id = if principal == principal_id {
+        teams::authorize(&principal_id, MemberRole::Viewer)?
+    } else {
+        teams::authorize(&principal_id, MemberRole::Owner)?
+    };
+
+    teams::remove(company_id, principal)?;
+
+    Ok(teams::members_of(&company_id))
+}
+
+#[query]
+fn get_company_members(company_id: Principal) -> Vec<CompanyMember> {
+    teams::members_of(&company_id)
+}
+
+#[query]
+fn get_invites() -> Vec<CompanyInvite> {
+    teams::invites_of(&ic_cdk::api::caller())
+}
+
+// ENDORSEMENTS
+//  applicants endorse each other's skills, a company attests the skills of someone it hired.
+
+#[update]
+fn endorse_skill(applicant_id: Principal, skill_id: u16) -> BoardResult<Endorsement> {
+    is_valid_applicant()?;
+
+    endorsements::endorse(ic_cdk::api::caller(), applicant_id, skill_id)
+}
+
+#[update]
+fn attest_skills(application_id: u64, skill_ids: Vec<u16>) -> BoardResult<Vec<Endorsement>> {
+    let principal_id = ic_cdk::api::caller();
+    let application = get_application_record(application_id)?;
+    let job = get_owned_job(application.job_id, principal_id, MemberRole::Recruiter)?;
+
+    if application.status != ApplicationStatus::Accepted {
+        return Err(BoardError::conflict(
+            "Only accepted applications can be attested",
+        ));
+    }
+
+    let applicant_id = application
+        .applicant_id
+        .ok_or_else(|| BoardError::not_found("applicant", application_id))?;
+    let company_id = job
+        .company_id
+        .ok_or_else(|| BoardError::not_found("company", job.id))?;
+
+    let profile_skills: BTreeSet<u16> = APPLICANT_PROFILE_STORE.with(|store| {
+        store
+            .borrow()
+            .get(&applicant_id)
+            .map(|profile| profile.skills.keys().cloned().collect())
+            .unwrap_or_default()
+    });
+    let allowed: BTreeSet<u16> = job
+        .required_skills
+        .keys()
+        .cloned()
+        .chain(profile_skills)
+        .collect();
+
+    endorsements::attest(
+        principal_id,
+        company_id,
+        application_id,
+        applicant_id,
+        skill_ids,
+        &allowed,
+    )
+}
+
+#[query]
+fn get_endorsements(
+    applicant_id: Principal,
+    request: PageRequest,
+) -> BoardResult<Page<Endorsement>> {
+    endorsements::ENDORSEMENT_STORE.with(|store| {
+        let store = store.borrow();
+        paginate_ids(&endorsements::of_applicant(&applicant_id), &request, |id| {
+            store.get(id).cloned()
+        })
+    })
+}
+
+#[query]
+fn get_skill_weights(applicant_id: Principal) -> Vec<SkillWeight> {
+    endorsements::weights(&applicant_id).into_values().collect()
+}
+
+// TALENT
+//  applicants opt in to being found by companies, contact details are shared once they accept a request.
+
+#[update]
+fn set_talent_settings(params: TalentParams) -> BoardResult<TalentSettings> {
+    is_valid_applicant()?;
+
+    talent::update(ic_cdk::api::caller(), params)
+}
+
+#[query]
+fn get_talent_settings() -> Option<TalentSettings> {
+    talent::settings(&ic_cdk::api::caller())
+}
+
+// ranked by skill overlap, the cursor is the score and id of the first hit on the next page
+#[query]
+fn search_talent(query: TalentQuery, request: PageRequest) -> BoardResult<Page<TalentHit>> {
+    teams::authorize(&ic_cdk::api::caller(), MemberRole::Viewer)?;
+
+    let ranked = talent::search(&query)?;
+
+    paginate_ranked(ranked, &request, |_, applicant_id| {
+        talent::hit(&query, applicant_id)
+    })
+}
+
+#[update]
+fn request_contact(applicant_id: Principal, message: String) -> BoardResult<ContactRequest> {
+    let principal_id = ic_cdk::api::caller();
+    let company_id = teams::authorize(&principal_id, MemberRole::Recruiter)?;
+
+    talent::request_contact(principal_id, company_id, applicant_id, message)
+}
+
+#[update]
+fn respond_to_contact(request_id: u64, accept: bool) -> BoardResult<ContactRequest> {
+    is_valid_applicant()?;
+
+    talent::respond(ic_cdk::api::caller(), request_id, accept)
+}
+
+#[query]
+fn applicant_contact_requests(request: PageRequest) -> BoardResult<Page<ContactRequest>> {
+    let ids = talent::ids_of_applicant(&ic_cdk::api::caller());
+
+    paginate_ids(&ids, &request, |id| talent::get(*id))
+}
+
+#[query]
+fn company_contact_requests(request: PageRequest) -> BoardResult<Page<ContactRequest>> {
+    let company_id = teams::authorize(&ic_cdk::api::caller(), MemberRole::Viewer)?;
+    let ids = talent::ids_of_company(&company_id);
+
+    paginate_ids(&ids, &request, |id| talent::get(*id))
+}
+
+// ALERTS
+//  applicants save searches and get a notification in their inbox for every new job matching one.
+
+#[update]
+fn create_saved_search(params: SavedSearchParams) -> BoardResult<SavedSearch> {
+    is_valid_applicant()?;
 
-    static JOB_ID_STORE: Cell<u64> = Cell::new(0);
-    static JOB_STORE: RefCell<JobStore> = RefCell::default();
+    alerts::create(ic_cdk::api::caller(), params)
+}
 
-    static JOB_BY_COMPANY_STORE: RefCell<JobByCompanyStore> = RefCell::default();
+#[update]
+fn update_saved_search(id: u64, params: SavedSearchParams) -> BoardResult<SavedSearch> {
+    alerts::update(ic_cdk::api::caller(), id, params)
+}
 
-    static APPLICATION_ID_STORE: Cell<u64> = Cell::new(0);
-    static APPLICATION_STORE: RefCell<ApplicationStore> = RefCell::default();
+#[update]
+fn delete_saved_search(id: u64) -> BoardResult<()> {
+    alerts::delete(ic_cdk::api::caller(), id)
+}
 
-    static SKILL_ID_STORE: Cell<u16> = Cell::new(0);
-    static SKILL_STORE: RefCell<SkillStore> = RefCell::default();
+#[query]
+fn get_saved_searches() -> Vec<SavedSearch> {
+    alerts::of_owner(&ic_cdk::api::caller())
 }
 
-fn is_valid_create_user() -> Result<(), String> {
+// INBOX
+//  job alerts and lifecycle updates of the caller's applications and jobs, oldest ones are dropped past a limit.
+
+#[query]
+fn get_notifications(request: PageRequest) -> BoardResult<Page<Notification>> {
     let principal_id = ic_cdk::api::caller();
 
-    let mut is_exist = false;
-    COMPANY_PROFILE_STORE.with(|profile_store| {
-        if !profile_store.borrow().get(&principal_id).is_none() {
-            is_exist = true;
-        };
-    });
+    inbox::INBOX_STORE.with(|store| {
+        let store = store.borrow();
+        let empty = BTreeMap::new();
+        let notifications = store.get(&principal_id).unwrap_or(&empty);
 
-    if is_exist {
-        return Err(String::from("User already exist as Company"));
-    };
+        paginate(notifications, &request, Notification::clone)
+    })
+}
 
-    let mut is_exist = false;
-    APPLICANT_PROFILE_STORE.with(|profile_store| {
-        if !profile_store.borrow().get(&principal_id).is_none() {
-            is_exist = true;
-        };
-    });
+#[query]
+fn get_unread_count() -> u64 {
+    inbox::unread_count(&ic_cdk::api::caller())
+}
 
-    if is_exist {
-        return Err(String::from("User already exist as Applicant"));
-    };
+#[update]
+fn mark_notifications_read(ids: Vec<u64>) -> u64 {
+    inbox::mark_read(&ic_cdk::api::caller(), &ids)
+}
 
-    Ok(())
+#[update]
+fn mark_all_notifications_read() -> u64 {
+    inbox::mark_all_read(&ic_cdk::api::caller())
 }
 
-fn is_valid_applicant() -> Result<(), String> {
-    let principal_id = ic_cdk::api::caller();
+// RECOMMENDATIONS
+//  ranked by skill overlap, required skills weigh more than nice to have ones and fresh records more than stale ones.
+//  the cursor is the score and id of the first match on the next page.
 
-    let mut is_exist = false;
-    APPLICANT_PROFILE_STORE.with(|profile_store| {
-        if !profile_store.borrow().get(&principal_id).is_none() {
-            is_exist = true;
-        };
-    });
+#[query]
+fn recommended_jobs(request: PageRequest) -> BoardResult<Page<JobRecommendation>> {
+    is_valid_applicant()?;
+    let applicant_id = ic_cdk::api::caller();
 
-    if is_exist {
-        return Err(String::from("User already exist as Applicant"));
-    };
+    paginate_ranked(recommend::jobs_for(&applicant_id), &request, |_, job_id| {
+        recommend::job_recommendation(&applicant_id, *job_id)
+    })
+}
 
-    Ok(())
+#[query]
+fn recommended_candidates(
+    job_id: u64,
+    request: PageRequest,
+) -> BoardResult<Page<CandidateRecommendation>> {
+    let job = get_owned_job(job_id, ic_cdk::api::caller(), MemberRole::Viewer)?;
+
+    paginate_ranked(
+        recommend::candidates_for(&job),
+        &request,
+        |_, applicant_id| recommend::candidate_recommendation(&job, applicant_id),
+    )
 }
 
-fn is_valid_company() -> Result<(), String> {
-    let principal_id = ic_cdk::api::caller();
+// BOUNTIES
+//  bounties are escrowed when a job is posted and paid out by the job and application transitions.
+//  a payout the ledger refused stays pending until it is retried.
 
-    let mut is_exist = false;
-    COMPANY_PROFILE_STORE.with(|profile_store| {
-        if !profile_store.borrow().get(&principal_id).is_none() {
-            is_exist = true;
-        };
-    });
+#[update]
+async fn retry_payout(job_id: u64) -> BoardResult<Escrow> {
+    let principal_id = ic_cdk::api::caller();
 
-    if !is_exist {
-        return Err(String::from("Invalid User"));
-    };
+    if !roles::is_moderator(&principal_id) {
+        get_owned_job(job_id, principal_id, MemberRole::Recruiter)?;
+    }
 
-    Ok(())
+    escrow::pay(job_id).await
 }
 
-fn update_skill(skills: Vec<Skill>) -> BTreeMap<u16, Skill> {
-    let mut updated_skills = BTreeMap::<u16, Skill>::new();
-
-    // not handling validations of skills,
-    // verify by id is easy, but to verify to string via on chain would require a bit of work with some limits imposed.
-    // because it could be an attacked vector if no limits.
-    // could use a different skill type for input and for storing, so don't need to store id as an option
-    SKILL_STORE.with(|skill_store| {
-        for skill in skills.iter() {
-            SKILL_ID_STORE.with(|id_store| {
-                if !skill.id.is_none() {
-                    updated_skills.insert(skill.id.unwrap(), skill.clone());
-                    return;
-                }
-
-                // fail safe to not overwrite existing skills if overflow
-                if id_store.get() != u16::MAX {
-                    return;
-                }
-
-                let id = id_store.get();
-                skill_store.borrow_mut().insert(
-                    id.to_owned(),
-                    Skill {
-                        id: Some(id.to_owned()),
-                        name: skill.name.clone(),
-                    },
-                );
-
-                updated_skills.insert(
-                    id.to_owned(),
-                    Skill {
-                        id: Some(id.to_owned()),
-                        name: skill.name.clone(),
-                    },
-                );
-
-                id_store.set(id + 1);
-            });
-        }
-    });
+#[query]
+fn get_escrow(job_id: u64) -> Option<Escrow> {
+    escrow::get(job_id)
+}
 
-    return updated_skills;
+#[query]
+fn get_ledger() -> Option<LedgerConfig> {
+    escrow::config()
 }
 
-#[update(guard = "is_valid_create_user")]
-fn create_applicant_profile(params: ApplicantParams, skills: Vec<Skill>) {
+// WEBHOOKS
+//  company owners register endpoints of their ATS. application events are sent to them
+//  as signed JSON through HTTPS outcalls, and failed sends are retried from a timer.
+
+#[update]
+fn register_webhook(params: WebhookParams) -> BoardResult<WebhookResponse> {
     let principal_id = ic_cdk::api::caller();
+    let company_id = teams::authorize(&principal_id, MemberRole::Owner)?;
 
-    APPLICANT_PROFILE_STORE.with(|profile_store| {
-        profile_store.borrow_mut().insert(
-            principal_id,
-            ApplicantProfile {
-                id: Some(principal_id),
-                first_name: params.first_name.to_lowercase(),
-                last_name: params.last_name.to_lowercase(),
-                nickname: params.nickname.to_lowercase(),
-                bio: params.bio.to_lowercase(),
-
-                // blocktime() -> is this a thing? will come back to this later
-                created_at: time(),
-                skills: update_skill(skills),
-            },
-        );
-    });
+    webhooks::register(company_id, principal_id, params).map(|webhook| (&webhook).into())
 }
 
-#[update(guard = "is_valid_create_user")]
-fn create_company_profile(params: CompanyParams) {
-    let principal_id = ic_cdk::api::caller();
+#[update]
+fn remove_webhook(id: u64) -> BoardResult<()> {
+    let company_id = teams::authorize(&ic_cdk::api::caller(), MemberRole::Owner)?;
 
-    COMPANY_PROFILE_STORE.with(|profile_store| {
-        profile_store.borrow_mut().insert(
-            principal_id,
-            CompanyProfile {
-                id: Some(principal_id),
-                name: params.name.to_lowercase(),
-                logo: params.logo.to_lowercase(),
-                twitter: params.twitter.to_lowercase(),
-                website: params.website.to_lowercase(),
-
-                // blocktime() -> is this a thing? will come back to this later
-                created_at: time(),
-            },
-        );
-    });
+    webhooks::remove(company_id, id)
 }
 
-#[update(guard = "is_valid_company")]
-fn create_job(params: JobParams, skills: Vec<Skill>) {
-    let principal_id = ic_cdk::api::caller();
+#[query]
+fn get_webhooks() -> BoardResult<Vec<WebhookResponse>> {
+    let company_id = teams::authorize(&ic_cdk::api::caller(), MemberRole::Owner)?;
 
-    JOB_ID_STORE.with(|id| {
-        JOB_STORE.with(|job_store| {
-            id.set(id.get() + 1);
-
-            job_store.borrow_mut().insert(
-                id.get(),
-                Job {
-                    id: id.get(),
-                    company_id: Some(principal_id),
-                    position: params.position.to_lowercase(),
-                    description: params.description.to_lowercase(),
-                    bounty: params.bounty,
-                    status: JobStatus::Open,
-                    required_skills: update_skill(skills),
-                },
-            );
-        });
-    });
+    Ok(webhooks::of_company(&company_id)
+        .iter()
+        .map(WebhookResponse::from)
+        .collect())
 }
 
-#[update(guard = "is_valid_applicant")]
-fn apply_to_job(params: ApplicationParams) {
-    let principal_id = ic_cdk::api::caller();
+// deliveries still being retried, and the ones that ran out of attempts
+#[query]
+fn get_webhook_deliveries(request: PageRequest) -> BoardResult<Page<Delivery>> {
+    let company_id = teams::authorize(&ic_cdk::api::caller(), MemberRole::Owner)?;
 
-    // VALIDATIONS
-    let mut is_exist_job_ = false;
-    JOB_STORE.with(|job_store| {
-        if job_store.borrow().get(&params.job_id).is_none() {
-            return;
-        }
+    paginate_ids(&webhooks::deliveries_of(&company_id), &request, |id| {
+        webhooks::get_delivery(*id)
+    })
+}
 
-        is_exist_job_ = true;
-    });
+#[update]
+fn retry_webhook_delivery(id: u64) -> BoardResult<Delivery> {
+    let company_id = teams::authorize(&ic_cdk::api::caller(), MemberRole::Owner)?;
 
-    if !is_exist_job_ {
-        return;
-    }
-
-    APPLICATION_ID_STORE.with(|id| {
-        APPLICATION_STORE.with(|application_store| {
-            id.set(id.get() + 1);
-
-            application_store.borrow_mut().insert(
-                id.get(),
-                Application {
-                    id: id.get(),
-                    applicant_id: Some(principal_id),
-                    job_id: params.job_id,
-                    status: ApplicationStatus::Applied,
-                    contact_email: params.contact_email.to_lowercase(),
-                    salary_from: params.salary_from,
-                    salary_to: params.salary_to,
-                },
-            );
-        });
-    });
+    webhooks::retry(company_id, id)
 }
 
+// called by the replicas on each outcall response, see webhooks::transform
+#[query]
+fn transform_webhook_response(args: TransformArgs) -> HttpResponse {
+    webhooks::transform(args)
+}
+
+// ADMIN
+//  admins manage roles, moderators (and admins) can act on any record.
+//  every action is written to the admin log together with the reason given.
+
 #[update]
-fn make_offer(appliation_id: u64, job_id: u64, accept: bool) {
-    let principal_id = ic_cdk::api::caller();
-    let mut is_valid = false;
+fn grant_role(principal: Principal, role: Role, reason: String) -> BoardResult<Vec<Role>> {
+    is_admin()?;
 
-    JOB_STORE.with(|store| {
-        if principal_id != store.borrow().get(&job_id).unwrap().company_id.unwrap() {
-            // unauthorized company id
-            return;
-        }
+    roles::grant(principal, role);
+    roles::log(
+        ic_cdk::api::caller(),
+        AdminAction::GrantRole { principal, role },
+        reason,
+    );
 
-        if job_id != store.borrow().get(&job_id).unwrap().id {
-            // invalid job id
-            return;
-        }
+    Ok(roles::roles_of(&principal))
+}
 
-        is_valid = true;
-    });
+#[update]
+fn revoke_role(principal: Principal, role: Role, reason: String) -> BoardResult<Vec<Role>> {
+    is_admin()?;
 
-    if !is_valid {
-        return;
-    }
+    roles::revoke(principal, role);
+    roles::log(
+        ic_cdk::api::caller(),
+        AdminAction::RevokeRole { principal, role },
+        reason,
+    );
 
-    APPLICATION_STORE.with(|store| {
-        if appliation_id <= store.borrow().len() as u64 {
-            // invalid appliction id
-            return;
-        }
+    Ok(roles::roles_of(&principal))
+}
 
-        let data = store.borrow();
-        let data = data.get(&appliation_id).unwrap();
-        if !(data.status == ApplicationStatus::Applied) {
-            // invalid application status
-            return;
-        }
+#[update]
+fn moderate_job_status(id: u64, status: JobStatus, reason: String) -> BoardResult<JobResponse> {
+    is_moderator()?;
 
-        if accept {
-            store.borrow_mut().insert(
-                appliation_id,
-                Application {
-                    status: ApplicationStatus::Offer,
-                    ..data.to_owned()
-                },
-            );
-        } else {
-            store.borrow_mut().insert(
-                appliation_id,
-                Application {
-                    status: ApplicationStatus::Rejected,
-                    ..data.to_owned()
-                },
-            );
-        }
-    })
+    let job = JOB_STORE
+        .with(|store| store.borrow().get(&id).cloned())
+        .ok_or_else(|| BoardError::not_found("job", id))?;
+    let job = transition::job(job, status.clone(), Actor::Admin)?;
+
+    roles::log(
+        ic_cdk::api::caller(),
+        AdminAction::SetJobStatus { job_id: id, status },
+        reason,
+    );
+
+    Ok(JobResponse::from(&job))
 }
 
-// #[update]
-// fn make_offer_directly(applicant_id: Principal, job_id: u64, params: ApplicationParams) {}
+#[update]
+fn moderate_application_status(
+    id: u64,
+    status: ApplicationStatus,
+    reason: String,
+) -> BoardResult<Application> {
+    is_moderator()?;
+
+    let application = get_application_record(id)?;
+    let application = transition::application(application, status.clone(), Actor::Admin)?;
+
+    roles::log(
+        ic_cdk::api::caller(),
+        AdminAction::SetApplicationStatus {
+            application_id: id,
+            status,
+        },
+        reason,
+    );
+
+    Ok(application)
+}
 
 #[update]
-fn accpet_offer(id: u64, accept: bool) {
-    let principal_id = ic_cdk::api::caller();
+fn moderate_job(id: u64, params: UpdateJobParams, reason: String) -> BoardResult<JobResponse> {
+    is_moderator()?;
 
-    APPLICATION_STORE.with(|store| {
-        if id <= store.borrow().len() as u64 {
-            // invalid appliction id
-            return;
-        }
+    let job = JOB_STORE
+        .with(|store| store.borrow().get(&id).cloned())
+        .ok_or_else(|| BoardError::not_found("job", id))?;
+    let job = edit_job(job, params)?;
 
-        let data = store.borrow();
-        let data = data.get(&id).unwrap();
+    roles::log(
+        ic_cdk::api::caller(),
+        AdminAction::UpdateJob { job_id: id },
+        reason,
+    );
 
-        if principal_id != data.applicant_id.unwrap() {
-            // invalid applicant
-            return;
-        }
+    Ok(job)
+}
 
-        if !(data.status == ApplicationStatus::Offer) {
-            // invalid application status
-            return;
-        }
+#[update]
+fn moderate_applicant_profile(
+    principal: Principal,
+    params: UpdateApplicantParams,
+    reason: String,
+) -> BoardResult<ApplicantProfileResponse> {
+    is_moderator()?;
+
+    let profile = edit_applicant_profile(principal, params)?;
+    roles::log(
+        ic_cdk::api::caller(),
+        AdminAction::UpdateApplicantProfile { principal },
+        reason,
+    );
+
+    Ok(profile)
+}
 
-        if accept {
-            store.borrow_mut().insert(
-                id,
-                Application {
-                    status: ApplicationStatus::Accepted,
-                    ..data.to_owned()
-                },
-            );
-        } else {
-            store.borrow_mut().insert(
-                id,
-                Application {
-                    status: ApplicationStatus::Rejected,
-                    ..data.to_owned()
-                },
-            );
-        }
-    })
+#[update]
+fn moderate_company_profile(
+    principal: Principal,
+    params: UpdateCompanyParams,
+    reason: String,
+) -> BoardResult<CompanyProfile> {
+    is_moderator()?;
+
+    let profile = edit_company_profile(principal, params)?;
+    roles::log(
+        ic_cdk::api::caller(),
+        AdminAction::UpdateCompanyProfile { principal },
+        reason,
+    );
+
+    Ok(profile)
 }
 
 #[update]
-fn cancel_job(id: u64) {
-    let principal_id = ic_cdk::api::caller();
+fn add_skill_alias(alias: String, skill_id: u16, reason: String) -> BoardResult<Skill> {
+    is_admin()?;
+
+    let skill = skills::add_alias(&alias, skill_id)?;
+    roles::log(
+        ic_cdk::api::caller(),
+        AdminAction::AddSkillAlias {
+            alias: skills::normalize(&alias),
+            skill_id,
+        },
+        reason,
+    );
+
+    Ok(skill)
+}
 
-    JOB_STORE.with(|job_store| {
-        let job = job_store.borrow();
-        let job = job.get(&id);
+#[update]
+fn remove_skill_alias(alias: String, reason: String) -> BoardResult<()> {
+    is_admin()?;
+
+    skills::remove_alias(&alias)?;
+    roles::log(
+        ic_cdk::api::caller(),
+        AdminAction::RemoveSkillAlias {
+            alias: skills::normalize(&alias),
+        },
+        reason,
+    );
 
-        if job.is_none() {
-            // job doesn't exist
-            return;
-        }
+    Ok(())
+}
 
-        let job = job.unwrap().to_owned();
+#[update]
+fn merge_skills(from: u16, into: u16, reason: String) -> BoardResult<Skill> {
+    is_admin()?;
 
-        if job.company_id.unwrap() != principal_id {
-            // invalid authority
-            return;
-        }
+    let skill = skills::merge(from, into)?;
+    roles::log(
+        ic_cdk::api::caller(),
+        AdminAction::MergeSkills { from, into },
+        reason,
+    );
 
-        job_store.borrow_mut().insert(
-            id,
-            Job {
-                status: JobStatus::Canceled,
-                ..job
-            },
-        );
-    });
+    Ok(skill)
 }
 
 #[update]
-fn withdraw_application(id: u64) {
-    let principal_id = ic_cdk::api::caller();
-    APPLICATION_STORE.with(|application_store| {
-        let application = application_store.borrow();
-        let application = application.get(&id);
+fn set_skill_parent(
+    skill_id: u16,
+    parent_id: Option<u16>,
+    reason: String,
+) -> BoardResult<SkillNode> {
+    is_admin()?;
+
+    let node = skills::set_parent(skill_id, parent_id)?;
+    roles::log(
+        ic_cdk::api::caller(),
+        AdminAction::SetSkillParent {
+            skill_id,
+            parent_id,
+        },
+        reason,
+    );
+
+    Ok(node)
+}
 
-        if application.is_none() {
-            // application doesn't exist
-            return;
-        }
+#[update]
+fn sweep_skills(reason: String) -> BoardResult<Vec<Skill>> {
+    is_admin()?;
+
+    let removed = skills::sweep();
+    roles::log(
+        ic_cdk::api::caller(),
+        AdminAction::SweepSkills {
+            skill_ids: removed.iter().filter_map(|skill| skill.id).collect(),
+        },
+        reason,
+    );
+
+    Ok(removed)
+}
+
+// callers can read their own roles, admins anyone's
+#[query]
+fn get_roles(principal: Principal) -> BoardResult<Vec<Role>> {
+    if ic_cdk::api::caller() != principal {
+        is_admin()?;
+    }
 
-        let application = application.unwrap().to_owned();
+    Ok(roles::roles_of(&principal))
+}
 
-        if application.applicant_id.unwrap() != principal_id {
-            // invalid authority
-            return;
-        }
+#[query]
+fn get_admin_log(request: PageRequest) -> BoardResult<Page<AdminLogEntry>> {
+    is_moderator()?;
 
-        application_store.borrow_mut().insert(
-            id,
-            Application {
-                status: ApplicationStatus::Withdraw,
-                ..application
-            },
-        );
-    });
+    roles::ADMIN_LOG_STORE.with(|store| paginate(&store.borrow(), &request, AdminLogEntry::clone))
+}
+
+#[query]
+fn get_audit_log(filter: AuditFilter, request: PageRequest) -> BoardResult<Page<AuditEvent>> {
+    is_moderator()?;
+
+    audit::query(&filter, &request)
+}
+
+// public, so indexers can mirror the board. events carry no caller.
+#[query]
+fn get_events_since(seq: u64, limit: Option<u64>) -> EventBatch {
+    events::since(seq, limit)
 }
 
 #[query]
@@ -418,296 +1299,311 @@ fn get_company(id: Principal) -> Option<CompanyProfile> {
 
 #[query]
 fn get_applicant(id: Principal) -> Option<ApplicantProfileResponse> {
+    if !is_listed(&ic_cdk::api::caller(), &id) {
+        return None;
+    }
+
     APPLICANT_PROFILE_STORE.with(|profile_store| {
         profile_store
             .borrow()
             .get(&id)
-            .map(|profile| ApplicantProfileResponse {
-                id: profile.id,
-                first_name: profile.first_name.clone(),
-                last_name: profile.last_name.clone(),
-                nickname: profile.nickname.clone(),
-                bio: profile.bio.clone(),
-                created_at: profile.created_at,
-                skills: profile.skills.values().cloned().collect(),
-            })
+            .map(ApplicantProfileResponse::from)
     })
 }
 
 #[query]
 fn get_job(id: u64) -> Option<JobResponse> {
-    JOB_STORE.with(|store| {
-        store.borrow().get(&id).map(|data| JobResponse {
-            id: data.id,
-            company_id: data.company_id,
-            position: data.position.clone(),
-            description: data.description.clone(),
-            bounty: data.bounty,
-            status: data.status.clone(),
-            required_skills: data.required_skills.values().cloned().collect(),
-        })
-    })
+    JOB_STORE.with(|store| store.borrow().get(&id).map(JobResponse::from))
 }
 
+// applications carry contact details and salary, so only the applicant, the hiring company
+// and moderators can read them
 #[query]
 fn get_application(id: u64) -> Option<Application> {
-    APPLICATION_STORE.with(|store| store.borrow().get(&id).map(|data| data.to_owned()))
+    let caller = ic_cdk::api::caller();
+
+    APPLICATION_STORE
+        .with(|store| store.borrow().get(&id).cloned())
+        .filter(|application| can_read_application(&caller, application))
 }
 
 #[query]
-fn get_skill_list(offset: u16, limit: u16) -> Option<Vec<Skill>> {
-    let mut data = Vec::<Skill>::new();
-
-    SKILL_STORE.with(|store| {
-        let len = store.borrow().len() as u16;
-
-        if len == 0 || offset >= len {
-            return;
-        }
+fn find_skill(name: String) -> Option<Skill> {
+    skills::find(&name)
+}
 
-        let start = offset;
-        let end = if offset + limit > len {
-            len - offset - 1
-        } else {
-            offset + limit
-        };
+#[query]
+fn get_skill_aliases(skill_id: u16) -> Vec<String> {
+    skills::aliases_of(skill_id)
+}
 
-        for (_, &ref skill) in store.borrow().range((Included(&start), Included(&end))) {
-            data.push(skill.to_owned());
-        }
-    });
+// the top of the taxonomy when `parent_id` is empty
+#[query]
+fn get_skill_children(parent_id: Option<u16>) -> Vec<SkillNode> {
+    let ids = match parent_id {
+        Some(parent_id) => skills::children_of(parent_id),
+        None => skills::roots(),
+    };
 
-    if data.len() == 0 {
-        None
-    } else {
-        Some(data)
-    }
+    ids.into_iter().filter_map(skills::node).collect()
 }
 
+// from the root down to the skill itself
 #[query]
-fn get_job_list(offset: u64, limit: u64) -> Option<Vec<JobResponse>> {
-    let mut data = Vec::<JobResponse>::new();
-
-    JOB_STORE.with(|store| {
-        let len = store.borrow().len() as u64;
+fn get_skill_path(skill_id: u16) -> Vec<Skill> {
+    let mut path: Vec<Skill> = skills::ancestors(skill_id)
+        .into_iter()
+        .rev()
+        .filter_map(skills::get)
+        .collect();
+    path.extend(skills::get(skill_id));
+
+    path
+}
 
-        if len == 0 || offset >= len {
-            return;
-        }
+#[query]
+fn get_skills_under(skill_id: u16, request: PageRequest) -> BoardResult<Page<Skill>> {
+    let mut ids = skills::subtree(skill_id);
+    ids.remove(&skill_id);
 
-        let start = offset;
-        let end = if offset + limit > len {
-            len - offset - 1
-        } else {
-            offset + limit
-        };
+    let skills: BTreeMap<u16, Skill> = ids
+        .into_iter()
+        .filter_map(|id| skills::get(id).map(|skill| (id, skill)))
+        .collect();
 
-        for (_, &ref job) in store.borrow().range((Included(&start), Included(&end))) {
-            data.push(JobResponse {
-                id: job.id,
-                company_id: job.company_id,
-                position: job.position.clone(),
-                description: job.description.clone(),
-                bounty: job.bounty,
-                status: job.status.clone(),
-                required_skills: job.required_skills.values().cloned().collect(),
-            });
-        }
-    });
+    paginate(&skills, &request, Skill::clone)
+}
 
-    if data.len() == 0 {
-        None
-    } else {
-        Some(data)
-    }
+#[query]
+fn get_skill_usage(skill_id: u16) -> Option<SkillUsage> {
+    skills::usage(skill_id)
 }
 
 #[query]
-fn get_application_list(offset: u64, limit: u64) -> Option<Vec<Application>> {
-    let mut data = Vec::<Application>::new();
+fn get_popular_skills(limit: u64) -> Vec<SkillUsage> {
+    skills::popular(limit.clamp(1, MAX_PAGE_SIZE) as usize)
+}
 
-    APPLICATION_STORE.with(|store| {
-        let len = store.borrow().len() as u64;
+#[query]
+fn get_skill_list(request: PageRequest) -> BoardResult<Page<Skill>> {
+    SKILL_STORE.with(|store| paginate(&store.borrow(), &request, Skill::clone))
+}
 
-        if len == 0 || offset >= len {
-            return;
-        }
+#[query]
+fn get_job_list(request: PageRequest) -> BoardResult<Page<JobResponse>> {
+    JOB_STORE.with(|store| paginate(&store.borrow(), &request, |job| JobResponse::from(job)))
+}
 
-        let start = offset;
-        let end = if offset + limit > len {
-            len - offset - 1
-        } else {
-            offset + limit
-        };
+// jobs passing every predicate of the filter, with facet counts over all of them
+#[query]
+fn filter_jobs(filter: JobFilter, request: PageRequest) -> BoardResult<FilteredJobs> {
+    let candidates = filter::candidates(&filter)?;
 
-        for (_, &ref value) in store.borrow().range((Included(&start), Included(&end))) {
-            data.push(value.to_owned());
-        }
-    });
+    let page = match &candidates {
+        Some(ids) => get_job_page(ids, &request)?,
+        None => get_job_list(request)?,
+    };
 
-    if data.len() == 0 {
-        None
-    } else {
-        Some(data)
-    }
+    Ok(FilteredJobs {
+        page,
+        facets: filter::facets(candidates.as_ref()),
+    })
 }
 
+// ranked by relevance, the cursor is the score and id of the first hit on the next page
 #[query]
-fn get_company_list(offset: u64, limit: u64) -> Option<Vec<CompanyProfile>> {
-    let mut data: Vec<CompanyProfile> = Vec::<CompanyProfile>::new();
+fn search_jobs(
+    query: String,
+    filter: Option<JobFilter>,
+    request: PageRequest,
+) -> BoardResult<Page<SearchHit>> {
+    let candidates = filter
+        .as_ref()
+        .map(filter::candidates)
+        .transpose()?
+        .flatten();
+    let ranked = search::search(&query, candidates.as_ref())?
+        .into_iter()
+        .map(|(id, score)| (score, id))
+        .collect();
 
-    COMPANY_PROFILE_STORE.with(|store| {
-        let len = store.borrow().len() as u64;
-
-        if len == 0 || offset >= len {
-            return;
-        }
+    JOB_STORE.with(|store| {
+        let store = store.borrow();
 
-        let start = offset;
-        let end = if offset + limit > len {
-            len - offset - 1
-        } else {
-            offset + limit
-        };
+        paginate_ranked(ranked, &request, |score, id| {
+            store.get(id).map(|job| SearchHit {
+                job: JobResponse::from(job),
+                score: *score,
+            })
+        })
+    })
+}
 
-        let list = store.borrow();
-        let list = list.iter().skip(start as usize).take(end as usize);
+// moderators page every application, anyone else their own and those of their company
+#[query]
+fn get_application_list(request: PageRequest) -> BoardResult<Page<Application>> {
+    let caller = ic_cdk::api::caller();
 
-        for (_, value) in list {
-            data.push(value.to_owned());
-        }
-    });
+    if roles::is_moderator(&caller) {
+        return APPLICATION_STORE
+            .with(|store| paginate(&store.borrow(), &request, Application::clone));
+    }
 
-    if data.len() == 0 {
-        None
-    } else {
-        Some(data)
+    let mut ids = index::applications_by_applicant(&caller);
+    if let Ok(company_id) = teams::authorize(&caller, MemberRole::Viewer) {
+        ids.extend(company_application_ids(&company_id));
     }
+
+    get_application_page(&ids, &request)
 }
 
 #[query]
-fn get_applicant_list(offset: u64, limit: u64) -> Option<Vec<ApplicantProfileResponse>> {
-    let mut data: Vec<ApplicantProfileResponse> = Vec::<ApplicantProfileResponse>::new();
+fn get_company_list(request: PageRequest) -> BoardResult<Page<CompanyProfile>> {
+    COMPANY_PROFILE_STORE.with(|store| paginate(&store.borrow(), &request, CompanyProfile::clone))
+}
+
+#[query]
+fn get_applicant_list(request: PageRequest) -> BoardResult<Page<ApplicantProfileResponse>> {
+    let caller = ic_cdk::api::caller();
 
     APPLICANT_PROFILE_STORE.with(|store| {
-        let len = store.borrow().len() as u64;
+        let store = store.borrow();
+        let listed: BTreeMap<Principal, ApplicantProfileResponse> = store
+            .iter()
+            .filter(|(id, _)| is_listed(&caller, id))
+            .map(|(id, profile)| (*id, profile.into()))
+            .collect();
+
+        paginate(&listed, &request, ApplicantProfileResponse::clone)
+    })
+}
 
-        if len == 0 || offset >= len {
-            return;
-        }
+// applicants stay hidden until they opt in to talent search, except from
+// themselves and from moderators
+fn is_listed(caller: &Principal, applicant_id: &Principal) -> bool {
+    caller == applicant_id || roles::is_moderator(caller) || talent::is_discoverable(applicant_id)
+}
 
-        let start = offset;
-        let end = if offset + limit > len {
-            len - offset - 1
-        } else {
-            offset + limit
-        };
+fn can_read_application(caller: &Principal, application: &Application) -> bool {
+    if application.applicant_id.as_ref() == Some(caller) || roles::is_moderator(caller) {
+        return true;
+    }
 
-        let list = store.borrow();
-        let list = list.iter().skip(start as usize).take(end as usize);
-
-        for (_, profile) in list {
-            data.push(ApplicantProfileResponse {
-                id: profile.id,
-                first_name: profile.first_name.clone(),
-                last_name: profile.last_name.clone(),
-                nickname: profile.nickname.clone(),
-                bio: profile.bio.clone(),
-                created_at: profile.created_at,
-                skills: profile.skills.values().cloned().collect(),
-            });
-        }
-    });
+    JOB_STORE
+        .with(|store| {
+            store
+                .borrow()
+                .get(&application.job_id)
+                .and_then(|job| job.company_id)
+        })
+        .is_some_and(|company_id| {
+            teams::authorize_for(&company_id, caller, MemberRole::Viewer).is_ok()
+        })
+}
 
-    if data.len() == 0 {
-        None
-    } else {
-        Some(data)
-    }
+fn company_application_ids(company_id: &Principal) -> BTreeSet<u64> {
+    index::jobs_by_company(company_id)
+        .iter()
+        .flat_map(index::applications_by_job)
+        .collect()
 }
 
-#[query]
-fn applicant_application_list(offset: u64, limit: u64) -> Option<Vec<Application>> {
-    let principal_id = ic_cdk::api::caller();
-    let mut data = Vec::<Application>::new();
+fn get_job_page(ids: &BTreeSet<u64>, request: &PageRequest) -> BoardResult<Page<JobResponse>> {
+    JOB_STORE.with(|store| {
+        let store = store.borrow();
+        paginate_ids(ids, request, |id| store.get(id).map(JobResponse::from))
+    })
+}
 
+fn get_application_page(
+    ids: &BTreeSet<u64>,
+    request: &PageRequest,
+) -> BoardResult<Page<Application>> {
     APPLICATION_STORE.with(|store| {
-        let len = store.borrow().len() as u64;
-
-        if len == 0 || offset >= len {
-            return;
-        }
+        let store = store.borrow();
+        paginate_ids(ids, request, |id| store.get(id).cloned())
+    })
+}
 
-        let start = offset;
-        for (_, &ref value) in store.borrow().range(start..) {
-            if data.len() == limit as usize {
-                break;
-            }
+#[query]
+fn applicant_application_list(request: PageRequest) -> BoardResult<Page<Application>> {
+    let principal_id = ic_cdk::api::caller();
+    let ids = index::applications_by_applicant(&principal_id);
 
-            if principal_id != value.applicant_id.unwrap() {
-                return;
-            }
+    get_application_page(&ids, &request)
+}
 
-            data.push(value.to_owned());
-        }
-    });
+#[query]
+fn company_application_list(request: PageRequest) -> BoardResult<Page<Application>> {
+    let company_id = teams::authorize(&ic_cdk::api::caller(), MemberRole::Viewer)?;
 
-    if data.len() == 0 {
-        None
-    } else {
-        Some(data)
-    }
+    get_application_page(&company_application_ids(&company_id), &request)
 }
 
 #[query]
-fn company_application_list(offset: u64, limit: u64) -> Option<Vec<Application>> {
+fn job_application_list(
+    job_id: u64,
+    status: Option<ApplicationStatus>,
+    request: PageRequest,
+) -> BoardResult<Page<Application>> {
     let principal_id = ic_cdk::api::caller();
-    let mut job_list: Option<Vec<u64>> = None;
-    let mut data = Vec::<Application>::new();
+    get_owned_job(job_id, principal_id, MemberRole::Viewer)?;
+
+    let mut ids = index::applications_by_job(&job_id);
+    if let Some(status) = status {
+        ids = ids
+            .intersection(&index::applications_by_status(&status))
+            .cloned()
+            .collect();
+    }
 
-    // I wonder because of the closure issue that this can be a performance issue
-    // if the job list is really long, because would have to move / clone everything.
-    JOB_BY_COMPANY_STORE.with(|store| {
-        let data = store.borrow();
-        let data = data.get(&principal_id);
+    get_application_page(&ids, &request)
+}
 
-        if data.is_some() {
-            job_list = Some(data.unwrap().to_owned())
-        }
-    });
+#[query]
+fn company_job_list(company_id: Principal, request: PageRequest) -> BoardResult<Page<JobResponse>> {
+    get_job_page(&index::jobs_by_company(&company_id), &request)
+}
 
-    if job_list.is_none() {
-        return None;
-    }
+#[query]
+fn job_list_by_status(status: JobStatus, request: PageRequest) -> BoardResult<Page<JobResponse>> {
+    get_job_page(&index::jobs_by_status(&status), &request)
+}
 
-    APPLICATION_STORE.with(|store| {
-        let len = store.borrow().len() as u64;
+// jobs asking for the skill or for anything below it in the taxonomy
+#[query]
+fn job_list_by_skill(skill_id: u16, request: PageRequest) -> BoardResult<Page<JobResponse>> {
+    let ids: BTreeSet<u64> = skills::subtree(skill_id)
+        .iter()
+        .flat_map(index::jobs_by_skill)
+        .collect();
 
-        if len == 0 || offset >= len {
-            return;
-        }
+    get_job_page(&ids, &request)
+}
 
-        let start = offset;
-        for (_, &ref value) in store.borrow().range(start..) {
-            if data.len() == limit as usize {
-                break;
-            }
-
-            if principal_id != value.applicant_id.unwrap() {
-                return;
-            }
-
-            // would be better to use BTreeMap
-            for job_id in job_list.clone().unwrap().as_slice() {
-                if job_id.clone() == value.job_id {
-                    data.push(value.to_owned());
-                    break;
-                }
-            }
-        }
-    });
+// applicants holding the skill or anything below it, so a job asking for
+// "systems programming" finds applicants with rust or c++
+#[query]
+fn applicant_list_by_skill(
+    skill_id: u16,
+    request: PageRequest,
+) -> BoardResult<Page<ApplicantProfileResponse>> {
+    let ids: BTreeSet<Principal> = skills::subtree(skill_id)
+        .iter()
+        .flat_map(index::applicants_by_skill)
+        .collect();
+    let caller = ic_cdk::api::caller();
 
-    Some(data)
+    APPLICANT_PROFILE_STORE.with(|store| {
+        let store = store.borrow();
+        let matches: BTreeMap<Principal, ApplicantProfileResponse> = ids
+            .iter()
+            .filter(|id| is_listed(&caller, id))
+            .filter_map(|id| store.get(id).map(|profile| (*id, profile.into())))
+            .collect();
+
+        paginate(&matches, &request, ApplicantProfileResponse::clone)
+    })
 }
 
 ic_cdk::export_candid!();
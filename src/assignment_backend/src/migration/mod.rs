@@ -0,0 +1,269 @@
+use candid::{Decode, Encode};
+
+use crate::storage::StableState;
+use crate::{MigrationReport, StateSnapshot};
+
+// bump this whenever a persisted type changes shape,
+// and push the step that converts the previous version onto MIGRATIONS.
+// the one exception is a new `opt` field on a persisted record (StableState itself,
+// Job.expires_at, ...): candid decodes a missing `opt` field as null, so snapshots
+// saved without it still load and no step is needed. renaming, retyping or removing
+// a field, or adding one that isn't `opt`, always needs a new version.
+// either way, copy the change into the `vN` module of the current version too,
+// the next step decodes that module and would drop whatever it doesn't know about.
+pub const STATE_VERSION: u32 = 4;
+
+// takes the candid encoded state of one version and returns the encoded state of the next.
+// old shapes should be copied into a `vN` module next to the step, not imported from state.rs,
+// because state.rs only ever describes the current version.
+type Migration = fn(Vec<u8>) -> Result<Vec<u8>, String>;
+
+mod v1;
+mod v2;
+mod v3;
+mod v4;
+
+// MIGRATIONS[i] converts version i + 1 into version i + 2
+const MIGRATIONS: &[Migration] = &[v1_to_v2, v2_to_v3, v3_to_v4];
+
+fn v1_to_v2(data: Vec<u8>) -> Result<Vec<u8>, String> {
+    let state = Decode!(&data, v1::StableState).map_err(|err| err.to_string())?;
+
+    Encode!(&v2::StableState::from(state)).map_err(|err| err.to_string())
+}
+
+fn v2_to_v3(data: Vec<u8>) -> Result<Vec<u8>, String> {
+    let state = Decode!(&data, v2::StableState).map_err(|err| err.to_string())?;
+
+    Encode!(&v3::StableState::from(state)).map_err(|err| err.to_string())
+}
+
+fn v3_to_v4(data: Vec<u8>) -> Result<Vec<u8>, String> {
+    let state = Decode!(&data, v3::StableState).map_err(|err| err.to_string())?;
+
+    Encode!(&v4::StableState::from(state)).map_err(|err| err.to_string())
+}
+
+pub fn migrate(snapshot: StateSnapshot) -> Result<StableState, String> {
+    if snapshot.version == 0 || snapshot.version > STATE_VERSION {
+        return Err(format!(
+            "Unsupported state version {}, expected 1..={}",
+            snapshot.version, STATE_VERSION
+        ));
+    }
+
+    let mut data = snapshot.data;
+    for step in &MIGRATIONS[(snapshot.version - 1) as usize..] {
+        data = step(data)?;
+    }
+
+    Decode!(&data, StableState).map_err(|err| err.to_string())
+}
+
+// runs the whole chain without touching the live stores,
+// so an exported snapshot can be checked against new code before upgrading.
+pub fn dry_run(snapshot: StateSnapshot) -> Result<MigrationReport, String> {
+    let from_version = snapshot.version;
+    let state = migrate(snapshot)?;
+
+    Ok(MigrationReport {
+        from_version,
+        to_version: STATE_VERSION,
+        applicant_profiles: state.applicant_profiles.len() as u64,
+        company_profiles: state.company_profiles.len() as u64,
+        jobs: state.jobs.len() as u64,
+        applications: state.applications.len() as u64,
+        skills: state.skills.len() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::{CandidType, Deserialize, Principal};
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{JobStatus, Proficiency};
+
+    fn snapshot(version: u32) -> StateSnapshot {
+        StateSnapshot {
+            version,
+            data: Encode!(&StableState::default()).unwrap(),
+        }
+    }
+
+    fn v1_snapshot() -> StateSnapshot {
+        let company = Principal::from_slice(&[1]);
+        let applicant = Principal::from_slice(&[2]);
+        let rust = v1::Skill {
+            id: Some(1),
+            name: "rust".to_string(),
+        };
+
+        let state = v1::StableState {
+            applicant_profiles: BTreeMap::from([(
+                applicant,
+                v1::ApplicantProfile {
+                    id: Some(applicant),
+                    first_name: "ada".to_string(),
+                    last_name: "lovelace".to_string(),
+                    nickname: String::new(),
+                    bio: String::new(),
+                    created_at: 7,
+                    skills: BTreeMap::from([(1, rust.clone())]),
+                },
+            )]),
+            company_profiles: BTreeMap::from([(
+                company,
+                v1::CompanyProfile {
+                    id: Some(company),
+                    name: "acme".to_string(),
+                    logo: String::new(),
+                    twitter: String::new(),
+                    website: String::new(),
+                    created_at: 3,
+                },
+            )]),
+            job_id: 1,
+            jobs: BTreeMap::from([(
+                1,
+                v1::Job {
+                    id: 1,
+                    company_id: Some(company),
+                    position: "hire".to_string(),
+                    description: String::new(),
+                    bounty: 0,
+                    status: v3::JobStatus::Open,
+                    required_skills: BTreeMap::from([(1, rust.clone())]),
+                },
+            )]),
+            application_id: 1,
+            applications: BTreeMap::from([(
+                1,
+                v1::Application {
+                    id: 1,
+                    applicant_id: Some(applicant),
+                    job_id: 1,
+                    contact_email: "ada@example.com".to_string(),
+                    status: v3::ApplicationStatus::Applied,
+                    salary_from: 0,
+                    salary_to: 0,
+                },
+            )]),
+            skill_id: 1,
+            skills: BTreeMap::from([(1, rust)]),
+        };
+
+        StateSnapshot {
+            version: 1,
+            data: Encode!(&state).unwrap(),
+        }
+    }
+
+    #[test]
+    fn dry_run_of_a_current_snapshot_has_nothing_to_migrate() {
+        let report = dry_run(snapshot(STATE_VERSION)).unwrap();
+
+        assert_eq!(report.from_version, STATE_VERSION);
+        assert_eq!(report.to_version, STATE_VERSION);
+        assert_eq!(report.jobs, 0);
+    }
+
+    #[test]
+    fn dry_run_counts_the_migrated_records() {
+        let report = dry_run(v1_snapshot()).unwrap();
+
+        assert_eq!(report.from_version, 1);
+        assert_eq!(report.to_version, STATE_VERSION);
+        assert_eq!(report.applicant_profiles, 1);
+        assert_eq!(report.company_profiles, 1);
+        assert_eq!(report.jobs, 1);
+        assert_eq!(report.applications, 1);
+        assert_eq!(report.skills, 1);
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        for version in [0, STATE_VERSION + 1] {
+            assert!(migrate(snapshot(version)).is_err());
+        }
+    }
+
+    // v1 -> v2
+    #[test]
+    fn updated_at_starts_at_created_at() {
+        let state = migrate(v1_snapshot()).unwrap();
+
+        let company = &state.company_profiles[&Principal::from_slice(&[1])];
+        assert_eq!(company.updated_at, 3);
+
+        let profile = &state.applicant_profiles[&Principal::from_slice(&[2])];
+        assert_eq!(profile.updated_at, 7);
+
+        assert_eq!(state.jobs[&1].updated_at, 0);
+        assert_eq!(state.applications[&1].updated_at, 0);
+        assert_eq!(state.job_id, 1);
+        assert_eq!(state.application_id, 1);
+        assert_eq!(state.skill_id, 1);
+    }
+
+    // v2 -> v3
+    #[test]
+    fn skills_start_at_the_lowest_level() {
+        let state = migrate(v1_snapshot()).unwrap();
+
+        let profile = &state.applicant_profiles[&Principal::from_slice(&[2])];
+        assert_eq!(profile.skills[&1].skill.name, "rust");
+        assert_eq!(profile.skills[&1].proficiency, Proficiency::Beginner);
+        assert_eq!(profile.skills[&1].years, 0);
+
+        let job = &state.jobs[&1];
+        assert_eq!(job.required_skills[&1].skill.name, "rust");
+        assert!(job.required_skills[&1].required);
+    }
+
+    // v3 -> v4
+    #[test]
+    fn jobs_are_dated_by_their_last_update() {
+        let state = migrate(v1_snapshot()).unwrap();
+        let job = &state.jobs[&1];
+
+        assert_eq!(job.status, JobStatus::Open);
+        assert_eq!(job.created_at, job.updated_at);
+    }
+
+    // a job as saved before expires_at and expired_at were added
+    #[derive(CandidType, Deserialize)]
+    struct JobWithoutExpiry {
+        id: u64,
+        company_id: Option<Principal>,
+        position: String,
+        description: String,
+        bounty: u64,
+        status: JobStatus,
+        created_at: u64,
+        updated_at: u64,
+        required_skills: BTreeMap<u16, crate::JobSkill>,
+    }
+
+    #[test]
+    fn new_opt_fields_decode_as_none() {
+        let job = JobWithoutExpiry {
+            id: 1,
+            company_id: None,
+            position: "hire".to_string(),
+            description: String::new(),
+            bounty: 0,
+            status: JobStatus::Closed,
+            created_at: 1,
+            updated_at: 2,
+            required_skills: BTreeMap::new(),
+        };
+
+        let job = Decode!(&Encode!(&job).unwrap(), crate::Job).unwrap();
+
+        assert_eq!(job.status, JobStatus::Closed);
+        assert_eq!(job.expires_at, None);
+        assert_eq!(job.expired_at, None);
+    }
+}
@@ -0,0 +1,177 @@
+// state as saved by STATE_VERSION 1, frozen so older snapshots keep decoding.
+use candid::{CandidType, Deserialize, Principal};
+use std::collections::BTreeMap;
+
+use super::v2;
+use super::v3::{self, ApplicationStatus, JobStatus};
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct CompanyProfile {
+    pub id: Option<Principal>,
+    pub name: String,
+    pub logo: String,
+    pub twitter: String,
+    pub website: String,
+    pub created_at: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct ApplicantProfile {
+    pub id: Option<Principal>,
+    pub first_name: String,
+    pub last_name: String,
+    pub nickname: String,
+    pub bio: String,
+    pub created_at: u64,
+    pub skills: BTreeMap<u16, Skill>,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct Skill {
+    pub id: Option<u16>,
+    pub name: String,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    pub company_id: Option<Principal>,
+    pub position: String,
+    pub description: String,
+    pub bounty: u64,
+    pub status: JobStatus,
+    pub required_skills: BTreeMap<u16, Skill>,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct Application {
+    pub id: u64,
+    pub applicant_id: Option<Principal>,
+    pub job_id: u64,
+    pub contact_email: String,
+    pub status: ApplicationStatus,
+    pub salary_from: u64,
+    pub salary_to: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct StableState {
+    pub applicant_profiles: BTreeMap<Principal, ApplicantProfile>,
+    pub company_profiles: BTreeMap<Principal, CompanyProfile>,
+    pub job_id: u64,
+    pub jobs: BTreeMap<u64, Job>,
+    pub application_id: u64,
+    pub applications: BTreeMap<u64, Application>,
+    pub skill_id: u16,
+    pub skills: BTreeMap<u16, Skill>,
+}
+
+// v2 added `updated_at` to profiles, jobs and applications.
+// records that never changed since v1 report their creation time, or 0 when they have none.
+impl From<Skill> for v3::Skill {
+    fn from(skill: Skill) -> Self {
+        Self {
+            id: skill.id,
+            name: skill.name,
+        }
+    }
+}
+
+fn skills(skills: BTreeMap<u16, Skill>) -> BTreeMap<u16, v3::Skill> {
+    skills
+        .into_iter()
+        .map(|(id, skill)| (id, skill.into()))
+        .collect()
+}
+
+impl From<CompanyProfile> for v3::CompanyProfile {
+    fn from(profile: CompanyProfile) -> Self {
+        Self {
+            id: profile.id,
+            name: profile.name,
+            logo: profile.logo,
+            twitter: profile.twitter,
+            website: profile.website,
+            created_at: profile.created_at,
+            updated_at: profile.created_at,
+        }
+    }
+}
+
+impl From<ApplicantProfile> for v2::ApplicantProfile {
+    fn from(profile: ApplicantProfile) -> Self {
+        Self {
+            id: profile.id,
+            first_name: profile.first_name,
+            last_name: profile.last_name,
+            nickname: profile.nickname,
+            bio: profile.bio,
+            created_at: profile.created_at,
+            updated_at: profile.created_at,
+            skills: skills(profile.skills),
+        }
+    }
+}
+
+impl From<Job> for v2::Job {
+    fn from(job: Job) -> Self {
+        Self {
+            id: job.id,
+            company_id: job.company_id,
+            position: job.position,
+            description: job.description,
+            bounty: job.bounty,
+            status: job.status,
+            required_skills: skills(job.required_skills),
+            updated_at: 0,
+        }
+    }
+}
+
+impl From<Application> for v3::Application {
+    fn from(application: Application) -> Self {
+        Self {
+            id: application.id,
+            applicant_id: application.applicant_id,
+            job_id: application.job_id,
+            contact_email: application.contact_email,
+            status: application.status,
+            salary_from: application.salary_from,
+            salary_to: application.salary_to,
+            updated_at: 0,
+            referrer: None,
+        }
+    }
+}
+
+impl From<StableState> for v2::StableState {
+    fn from(state: StableState) -> Self {
+        Self {
+            applicant_profiles: state
+                .applicant_profiles
+                .into_iter()
+                .map(|(id, profile)| (id, profile.into()))
+                .collect(),
+            company_profiles: state
+                .company_profiles
+                .into_iter()
+                .map(|(id, profile)| (id, profile.into()))
+                .collect(),
+            job_id: state.job_id,
+            jobs: state
+                .jobs
+                .into_iter()
+                .map(|(id, job)| (id, job.into()))
+                .collect(),
+            application_id: state.application_id,
+            applications: state
+                .applications
+                .into_iter()
+                .map(|(id, application)| (id, application.into()))
+                .collect(),
+            skill_id: state.skill_id,
+            skills: skills(state.skills),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,139 @@
+// state as saved by STATE_VERSION 2, frozen so older snapshots keep decoding.
+// only the records v3 changed are copied here, everything else is taken from v3.
+use candid::{CandidType, Deserialize, Principal};
+use std::collections::BTreeMap;
+
+use super::v3::{self, Application, CompanyProfile, JobStatus, Skill};
+use super::v3::{AdminLogStore, CompanyInviteStore, CompanyMemberStore, EscrowStore, RoleStore};
+use super::v3::{LedgerConfig, SkillAliasStore, SkillParentStore};
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct ApplicantProfile {
+    pub id: Option<Principal>,
+    pub first_name: String,
+    pub last_name: String,
+    pub nickname: String,
+    pub bio: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub skills: BTreeMap<u16, Skill>,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    pub company_id: Option<Principal>,
+    pub position: String,
+    pub description: String,
+    pub bounty: u64,
+    pub status: JobStatus,
+    pub updated_at: u64,
+    pub required_skills: BTreeMap<u16, Skill>,
+}
+
+#[derive(Default, CandidType, Deserialize)]
+pub struct StableState {
+    pub applicant_profiles: BTreeMap<Principal, ApplicantProfile>,
+    pub company_profiles: BTreeMap<Principal, CompanyProfile>,
+    pub job_id: u64,
+    pub jobs: BTreeMap<u64, Job>,
+    pub application_id: u64,
+    pub applications: BTreeMap<u64, Application>,
+    pub skill_id: u16,
+    pub skills: BTreeMap<u16, Skill>,
+    pub roles: Option<RoleStore>,
+    pub admin_log_id: Option<u64>,
+    pub admin_log: Option<AdminLogStore>,
+    pub company_members: Option<CompanyMemberStore>,
+    pub company_invites: Option<CompanyInviteStore>,
+    pub ledger: Option<LedgerConfig>,
+    pub escrows: Option<EscrowStore>,
+    pub skill_aliases: Option<SkillAliasStore>,
+    pub skill_parents: Option<SkillParentStore>,
+}
+
+// v3 gave every skill on a profile or job a proficiency and years of experience.
+// existing entries become the lowest level, and every skill a job listed stays required.
+impl From<ApplicantProfile> for v3::ApplicantProfile {
+    fn from(profile: ApplicantProfile) -> Self {
+        Self {
+            id: profile.id,
+            first_name: profile.first_name,
+            last_name: profile.last_name,
+            nickname: profile.nickname,
+            bio: profile.bio,
+            created_at: profile.created_at,
+            updated_at: profile.updated_at,
+            skills: profile
+                .skills
+                .into_iter()
+                .map(|(id, skill)| {
+                    let entry = v3::ApplicantSkill {
+                        skill,
+                        ..Default::default()
+                    };
+                    (id, entry)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<Job> for v3::Job {
+    fn from(job: Job) -> Self {
+        Self {
+            id: job.id,
+            company_id: job.company_id,
+            position: job.position,
+            description: job.description,
+            bounty: job.bounty,
+            status: job.status,
+            updated_at: job.updated_at,
+            required_skills: job
+                .required_skills
+                .into_iter()
+                .map(|(id, skill)| {
+                    let entry = v3::JobSkill {
+                        skill,
+                        required: true,
+                        ..Default::default()
+                    };
+                    (id, entry)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<StableState> for v3::StableState {
+    fn from(state: StableState) -> Self {
+        Self {
+            applicant_profiles: state
+                .applicant_profiles
+                .into_iter()
+                .map(|(id, profile)| (id, profile.into()))
+                .collect(),
+            company_profiles: state.company_profiles,
+            job_id: state.job_id,
+            jobs: state
+                .jobs
+                .into_iter()
+                .map(|(id, job)| (id, job.into()))
+                .collect(),
+            application_id: state.application_id,
+            applications: state.applications,
+            skill_id: state.skill_id,
+            skills: state.skills,
+            roles: state.roles,
+            admin_log_id: state.admin_log_id,
+            admin_log: state.admin_log,
+            company_members: state.company_members,
+            company_invites: state.company_invites,
+            ledger: state.ledger,
+            escrows: state.escrows,
+            skill_aliases: state.skill_aliases,
+            skill_parents: state.skill_parents,
+            ..Default::default()
+        }
+    }
+}
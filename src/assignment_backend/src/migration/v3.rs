@@ -0,0 +1,650 @@
+// state as saved by STATE_VERSION 3, frozen so older snapshots keep decoding.
+// every record is copied here, v1 and v2 reuse the ones they share with v3.
+use candid::{CandidType, Deserialize, Nat, Principal};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::v4;
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct CompanyProfile {
+    pub id: Option<Principal>,
+    pub name: String,
+    pub logo: String,
+    pub twitter: String,
+    pub website: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct ApplicantProfile {
+    pub id: Option<Principal>,
+    pub first_name: String,
+    pub last_name: String,
+    pub nickname: String,
+    pub bio: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub skills: BTreeMap<u16, ApplicantSkill>,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct Skill {
+    pub id: Option<u16>,
+    pub name: String,
+}
+
+#[derive(Clone, Copy, Default, CandidType, Deserialize)]
+pub enum Proficiency {
+    #[default]
+    Beginner,
+    Intermediate,
+    Advanced,
+    Expert,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct ApplicantSkill {
+    pub skill: Skill,
+    pub proficiency: Proficiency,
+    pub years: u8,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct JobSkill {
+    pub skill: Skill,
+    pub proficiency: Proficiency,
+    pub years: u8,
+    pub required: bool,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub enum JobStatus {
+    #[default]
+    Open,
+    Closed,
+    Canceled,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub enum ApplicationStatus {
+    #[default]
+    Applied,
+    Withdraw,
+    Offer,
+    Accepted,
+    Rejected,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    pub company_id: Option<Principal>,
+    pub position: String,
+    pub description: String,
+    pub bounty: u64,
+    pub status: JobStatus,
+    pub updated_at: u64,
+    pub required_skills: BTreeMap<u16, JobSkill>,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct Application {
+    pub id: u64,
+    pub applicant_id: Option<Principal>,
+    pub job_id: u64,
+    pub contact_email: String,
+    pub status: ApplicationStatus,
+    pub salary_from: u64,
+    pub salary_to: u64,
+    pub updated_at: u64,
+    pub referrer: Option<Principal>,
+}
+
+#[derive(Clone, Copy, CandidType, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Admin,
+    Moderator,
+    Company,
+    Applicant,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub enum AdminAction {
+    GrantRole {
+        principal: Principal,
+        role: Role,
+    },
+    RevokeRole {
+        principal: Principal,
+        role: Role,
+    },
+    SetJobStatus {
+        job_id: u64,
+        status: JobStatus,
+    },
+    SetApplicationStatus {
+        application_id: u64,
+        status: ApplicationStatus,
+    },
+    UpdateJob {
+        job_id: u64,
+    },
+    UpdateApplicantProfile {
+        principal: Principal,
+    },
+    UpdateCompanyProfile {
+        principal: Principal,
+    },
+    AddSkillAlias {
+        alias: String,
+        skill_id: u16,
+    },
+    RemoveSkillAlias {
+        alias: String,
+    },
+    MergeSkills {
+        from: u16,
+        into: u16,
+    },
+    SetSkillParent {
+        skill_id: u16,
+        parent_id: Option<u16>,
+    },
+    SweepSkills {
+        skill_ids: Vec<u16>,
+    },
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct AdminLogEntry {
+    pub id: u64,
+    pub admin: Principal,
+    pub action: AdminAction,
+    pub reason: String,
+    pub created_at: u64,
+}
+
+#[derive(Clone, Copy, CandidType, Deserialize)]
+pub enum MemberRole {
+    Viewer,
+    Recruiter,
+    Owner,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct CompanyMember {
+    pub principal: Principal,
+    pub role: MemberRole,
+    pub joined_at: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct CompanyInvite {
+    pub company_id: Principal,
+    pub principal: Principal,
+    pub role: MemberRole,
+    pub invited_by: Principal,
+    pub created_at: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct LedgerConfig {
+    pub canister_id: Principal,
+    pub token_symbol: String,
+    pub fee: u64,
+}
+
+#[derive(Clone, Copy, CandidType, Deserialize)]
+pub enum Payout {
+    Release,
+    Refund,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub enum EscrowStatus {
+    Held,
+    Paying {
+        payout: Payout,
+        to: Principal,
+        created_at_time: u64,
+        error: Option<String>,
+    },
+    Paid {
+        payout: Payout,
+        to: Principal,
+        block_index: Nat,
+    },
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct Escrow {
+    pub job_id: u64,
+    pub amount: u64,
+    pub funded_by: Principal,
+    pub funding_block: Nat,
+    pub status: EscrowStatus,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub enum EndorsementKind {
+    Endorsement,
+    Attestation {
+        company_id: Principal,
+        application_id: u64,
+    },
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct Endorsement {
+    pub id: u64,
+    pub applicant_id: Principal,
+    pub skill_id: u16,
+    pub issuer: Principal,
+    pub kind: EndorsementKind,
+    pub created_at: u64,
+}
+
+pub type RoleStore = BTreeMap<Principal, BTreeSet<Role>>;
+pub type AdminLogStore = BTreeMap<u64, AdminLogEntry>;
+pub type CompanyMemberStore = BTreeMap<Principal, BTreeMap<Principal, CompanyMember>>;
+pub type CompanyInviteStore = BTreeMap<Principal, BTreeMap<Principal, CompanyInvite>>;
+pub type EscrowStore = BTreeMap<u64, Escrow>;
+pub type SkillAliasStore = BTreeMap<String, u16>;
+pub type SkillParentStore = BTreeMap<u16, u16>;
+pub type EndorsementStore = BTreeMap<u64, Endorsement>;
+
+#[derive(Default, CandidType, Deserialize)]
+pub struct StableState {
+    pub applicant_profiles: BTreeMap<Principal, ApplicantProfile>,
+    pub company_profiles: BTreeMap<Principal, CompanyProfile>,
+    pub job_id: u64,
+    pub jobs: BTreeMap<u64, Job>,
+    pub application_id: u64,
+    pub applications: BTreeMap<u64, Application>,
+    pub skill_id: u16,
+    pub skills: BTreeMap<u16, Skill>,
+    pub roles: Option<RoleStore>,
+    pub admin_log_id: Option<u64>,
+    pub admin_log: Option<AdminLogStore>,
+    pub company_members: Option<CompanyMemberStore>,
+    pub company_invites: Option<CompanyInviteStore>,
+    pub ledger: Option<LedgerConfig>,
+    pub escrows: Option<EscrowStore>,
+    pub skill_aliases: Option<SkillAliasStore>,
+    pub skill_parents: Option<SkillParentStore>,
+    pub endorsement_id: Option<u64>,
+    pub endorsements: Option<EndorsementStore>,
+}
+
+fn convert<K: Ord, A, B: From<A>>(map: BTreeMap<K, A>) -> BTreeMap<K, B> {
+    map.into_iter()
+        .map(|(key, value)| (key, value.into()))
+        .collect()
+}
+
+// records v4 left alone convert field by field
+
+impl From<CompanyProfile> for v4::CompanyProfile {
+    fn from(profile: CompanyProfile) -> Self {
+        Self {
+            id: profile.id,
+            name: profile.name,
+            logo: profile.logo,
+            twitter: profile.twitter,
+            website: profile.website,
+            created_at: profile.created_at,
+            updated_at: profile.updated_at,
+        }
+    }
+}
+
+impl From<ApplicantProfile> for v4::ApplicantProfile {
+    fn from(profile: ApplicantProfile) -> Self {
+        Self {
+            id: profile.id,
+            first_name: profile.first_name,
+            last_name: profile.last_name,
+            nickname: profile.nickname,
+            bio: profile.bio,
+            created_at: profile.created_at,
+            updated_at: profile.updated_at,
+            skills: convert(profile.skills),
+        }
+    }
+}
+
+impl From<Skill> for v4::Skill {
+    fn from(skill: Skill) -> Self {
+        Self {
+            id: skill.id,
+            name: skill.name,
+        }
+    }
+}
+
+impl From<Proficiency> for v4::Proficiency {
+    fn from(proficiency: Proficiency) -> Self {
+        match proficiency {
+            Proficiency::Beginner => Self::Beginner,
+            Proficiency::Intermediate => Self::Intermediate,
+            Proficiency::Advanced => Self::Advanced,
+            Proficiency::Expert => Self::Expert,
+        }
+    }
+}
+
+impl From<ApplicantSkill> for v4::ApplicantSkill {
+    fn from(entry: ApplicantSkill) -> Self {
+        Self {
+            skill: entry.skill.into(),
+            proficiency: entry.proficiency.into(),
+            years: entry.years,
+        }
+    }
+}
+
+impl From<JobSkill> for v4::JobSkill {
+    fn from(entry: JobSkill) -> Self {
+        Self {
+            skill: entry.skill.into(),
+            proficiency: entry.proficiency.into(),
+            years: entry.years,
+            required: entry.required,
+        }
+    }
+}
+
+impl From<JobStatus> for v4::JobStatus {
+    fn from(status: JobStatus) -> Self {
+        match status {
+            JobStatus::Open => Self::Open,
+            JobStatus::Closed => Self::Closed,
+            JobStatus::Canceled => Self::Canceled,
+        }
+    }
+}
+
+impl From<ApplicationStatus> for v4::ApplicationStatus {
+    fn from(status: ApplicationStatus) -> Self {
+        match status {
+            ApplicationStatus::Applied => Self::Applied,
+            ApplicationStatus::Withdraw => Self::Withdraw,
+            ApplicationStatus::Offer => Self::Offer,
+            ApplicationStatus::Accepted => Self::Accepted,
+            ApplicationStatus::Rejected => Self::Rejected,
+        }
+    }
+}
+
+impl From<Application> for v4::Application {
+    fn from(application: Application) -> Self {
+        Self {
+            id: application.id,
+            applicant_id: application.applicant_id,
+            job_id: application.job_id,
+            contact_email: application.contact_email,
+            status: application.status.into(),
+            salary_from: application.salary_from,
+            salary_to: application.salary_to,
+            updated_at: application.updated_at,
+            referrer: application.referrer,
+        }
+    }
+}
+
+impl From<Role> for v4::Role {
+    fn from(role: Role) -> Self {
+        match role {
+            Role::Admin => Self::Admin,
+            Role::Moderator => Self::Moderator,
+            Role::Company => Self::Company,
+            Role::Applicant => Self::Applicant,
+        }
+    }
+}
+
+impl From<AdminAction> for v4::AdminAction {
+    fn from(action: AdminAction) -> Self {
+        match action {
+            AdminAction::GrantRole { principal, role } => Self::GrantRole {
+                principal,
+                role: role.into(),
+            },
+            AdminAction::RevokeRole { principal, role } => Self::RevokeRole {
+                principal,
+                role: role.into(),
+            },
+            AdminAction::SetJobStatus { job_id, status } => Self::SetJobStatus {
+                job_id,
+                status: status.into(),
+            },
+            AdminAction::SetApplicationStatus {
+                application_id,
+                status,
+            } => Self::SetApplicationStatus {
+                application_id,
+                status: status.into(),
+            },
+            AdminAction::UpdateJob { job_id } => Self::UpdateJob { job_id },
+            AdminAction::UpdateApplicantProfile { principal } => {
+                Self::UpdateApplicantProfile { principal }
+            }
+            AdminAction::UpdateCompanyProfile { principal } => {
+                Self::UpdateCompanyProfile { principal }
+            }
+            AdminAction::AddSkillAlias { alias, skill_id } => {
+                Self::AddSkillAlias { alias, skill_id }
+            }
+            AdminAction::RemoveSkillAlias { alias } => Self::RemoveSkillAlias { alias },
+            AdminAction::MergeSkills { from, into } => Self::MergeSkills { from, into },
+            AdminAction::SetSkillParent {
+                skill_id,
+                parent_id,
+            } => Self::SetSkillParent {
+                skill_id,
+                parent_id,
+            },
+            AdminAction::SweepSkills { skill_ids } => Self::SweepSkills { skill_ids },
+        }
+    }
+}
+
+impl From<AdminLogEntry> for v4::AdminLogEntry {
+    fn from(entry: AdminLogEntry) -> Self {
+        Self {
+            id: entry.id,
+            admin: entry.admin,
+            action: entry.action.into(),
+            reason: entry.reason,
+            created_at: entry.created_at,
+        }
+    }
+}
+
+impl From<MemberRole> for v4::MemberRole {
+    fn from(role: MemberRole) -> Self {
+        match role {
+            MemberRole::Viewer => Self::Viewer,
+            MemberRole::Recruiter => Self::Recruiter,
+            MemberRole::Owner => Self::Owner,
+        }
+    }
+}
+
+impl From<CompanyMember> for v4::CompanyMember {
+    fn from(member: CompanyMember) -> Self {
+        Self {
+            principal: member.principal,
+            role: member.role.into(),
+            joined_at: member.joined_at,
+        }
+    }
+}
+
+impl From<CompanyInvite> for v4::CompanyInvite {
+    fn from(invite: CompanyInvite) -> Self {
+        Self {
+            company_id: invite.company_id,
+            principal: invite.principal,
+            role: invite.role.into(),
+            invited_by: invite.invited_by,
+            created_at: invite.created_at,
+        }
+    }
+}
+
+impl From<LedgerConfig> for v4::LedgerConfig {
+    fn from(ledger: LedgerConfig) -> Self {
+        Self {
+            canister_id: ledger.canister_id,
+            token_symbol: ledger.token_symbol,
+            fee: ledger.fee,
+        }
+    }
+}
+
+impl From<Payout> for v4::Payout {
+    fn from(payout: Payout) -> Self {
+        match payout {
+            Payout::Release => Self::Release,
+            Payout::Refund => Self::Refund,
+        }
+    }
+}
+
+impl From<EscrowStatus> for v4::EscrowStatus {
+    fn from(status: EscrowStatus) -> Self {
+        match status {
+            EscrowStatus::Held => Self::Held,
+            EscrowStatus::Paying {
+                payout,
+                to,
+                created_at_time,
+                error,
+            } => Self::Paying {
+                payout: payout.into(),
+                to,
+                created_at_time,
+                error,
+            },
+            EscrowStatus::Paid {
+                payout,
+                to,
+                block_index,
+            } => Self::Paid {
+                payout: payout.into(),
+                to,
+                block_index,
+            },
+        }
+    }
+}
+
+impl From<Escrow> for v4::Escrow {
+    fn from(escrow: Escrow) -> Self {
+        Self {
+            job_id: escrow.job_id,
+            amount: escrow.amount,
+            funded_by: escrow.funded_by,
+            funding_block: escrow.funding_block,
+            fee: None,
+            status: escrow.status.into(),
+            updated_at: escrow.updated_at,
+            subaccount: None,
+        }
+    }
+}
+
+impl From<EndorsementKind> for v4::EndorsementKind {
+    fn from(kind: EndorsementKind) -> Self {
+        match kind {
+            EndorsementKind::Endorsement => Self::Endorsement,
+            EndorsementKind::Attestation {
+                company_id,
+                application_id,
+            } => Self::Attestation {
+                company_id,
+                application_id,
+            },
+        }
+    }
+}
+
+impl From<Endorsement> for v4::Endorsement {
+    fn from(endorsement: Endorsement) -> Self {
+        Self {
+            id: endorsement.id,
+            applicant_id: endorsement.applicant_id,
+            skill_id: endorsement.skill_id,
+            issuer: endorsement.issuer,
+            kind: endorsement.kind.into(),
+            created_at: endorsement.created_at,
+        }
+    }
+}
+
+// v4 records when a job was posted. jobs saved before that never kept it,
+// the last update is the closest time we have.
+impl From<Job> for v4::Job {
+    fn from(job: Job) -> Self {
+        Self {
+            id: job.id,
+            company_id: job.company_id,
+            position: job.position,
+            description: job.description,
+            bounty: job.bounty,
+            status: job.status.into(),
+            created_at: job.updated_at,
+            updated_at: job.updated_at,
+            expires_at: None,
+            expired_at: None,
+            required_skills: convert(job.required_skills),
+        }
+    }
+}
+
+impl From<StableState> for v4::StableState {
+    fn from(state: StableState) -> Self {
+        Self {
+            applicant_profiles: convert(state.applicant_profiles),
+            company_profiles: convert(state.company_profiles),
+            job_id: state.job_id,
+            jobs: convert(state.jobs),
+            application_id: state.application_id,
+            applications: convert(state.applications),
+            skill_id: state.skill_id,
+            skills: convert(state.skills),
+            roles: state.roles.map(|roles| {
+                roles
+                    .into_iter()
+                    .map(|(principal, roles)| {
+                        (principal, roles.into_iter().map(Into::into).collect())
+                    })
+                    .collect()
+            }),
+            admin_log_id: state.admin_log_id,
+            admin_log: state.admin_log.map(convert),
+            company_members: state.company_members.map(|companies| {
+                companies
+                    .into_iter()
+                    .map(|(id, members)| (id, convert(members)))
+                    .collect()
+            }),
+            company_invites: state.company_invites.map(|invitees| {
+                invitees
+                    .into_iter()
+                    .map(|(id, invites)| (id, convert(invites)))
+                    .collect()
+            }),
+            ledger: state.ledger.map(Into::into),
+            escrows: state.escrows.map(convert),
+            skill_aliases: state.skill_aliases,
+            skill_parents: state.skill_parents,
+            endorsement_id: state.endorsement_id,
+            endorsements: state.endorsements.map(convert),
+            ..Default::default()
+        }
+    }
+}
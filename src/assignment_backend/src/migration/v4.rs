@@ -0,0 +1,401 @@
+// state as saved by STATE_VERSION 4, frozen so v3 snapshots have a fixed shape to
+// convert into. until v5 exists it has to match storage.rs field for field,
+// the `opt` fields added since the bump included.
+use candid::{CandidType, Deserialize, Nat, Principal};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct CompanyProfile {
+    pub id: Option<Principal>,
+    pub name: String,
+    pub logo: String,
+    pub twitter: String,
+    pub website: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct ApplicantProfile {
+    pub id: Option<Principal>,
+    pub first_name: String,
+    pub last_name: String,
+    pub nickname: String,
+    pub bio: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub skills: BTreeMap<u16, ApplicantSkill>,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct Skill {
+    pub id: Option<u16>,
+    pub name: String,
+}
+
+#[derive(Clone, Copy, Default, CandidType, Deserialize)]
+pub enum Proficiency {
+    #[default]
+    Beginner,
+    Intermediate,
+    Advanced,
+    Expert,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct ApplicantSkill {
+    pub skill: Skill,
+    pub proficiency: Proficiency,
+    pub years: u8,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct JobSkill {
+    pub skill: Skill,
+    pub proficiency: Proficiency,
+    pub years: u8,
+    pub required: bool,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub enum JobStatus {
+    #[default]
+    Open,
+    Closed,
+    Canceled,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub enum ApplicationStatus {
+    #[default]
+    Applied,
+    Withdraw,
+    Offer,
+    Accepted,
+    Rejected,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    pub company_id: Option<Principal>,
+    pub position: String,
+    pub description: String,
+    pub bounty: u64,
+    pub status: JobStatus,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub expires_at: Option<u64>,
+    pub expired_at: Option<u64>,
+    pub required_skills: BTreeMap<u16, JobSkill>,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct Application {
+    pub id: u64,
+    pub applicant_id: Option<Principal>,
+    pub job_id: u64,
+    pub contact_email: String,
+    pub status: ApplicationStatus,
+    pub salary_from: u64,
+    pub salary_to: u64,
+    pub updated_at: u64,
+    pub referrer: Option<Principal>,
+}
+
+#[derive(Clone, Copy, CandidType, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Admin,
+    Moderator,
+    Company,
+    Applicant,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub enum AdminAction {
+    GrantRole {
+        principal: Principal,
+        role: Role,
+    },
+    RevokeRole {
+        principal: Principal,
+        role: Role,
+    },
+    SetJobStatus {
+        job_id: u64,
+        status: JobStatus,
+    },
+    SetApplicationStatus {
+        application_id: u64,
+        status: ApplicationStatus,
+    },
+    UpdateJob {
+        job_id: u64,
+    },
+    UpdateApplicantProfile {
+        principal: Principal,
+    },
+    UpdateCompanyProfile {
+        principal: Principal,
+    },
+    AddSkillAlias {
+        alias: String,
+        skill_id: u16,
+    },
+    RemoveSkillAlias {
+        alias: String,
+    },
+    MergeSkills {
+        from: u16,
+        into: u16,
+    },
+    SetSkillParent {
+        skill_id: u16,
+        parent_id: Option<u16>,
+    },
+    SweepSkills {
+        skill_ids: Vec<u16>,
+    },
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct AdminLogEntry {
+    pub id: u64,
+    pub admin: Principal,
+    pub action: AdminAction,
+    pub reason: String,
+    pub created_at: u64,
+}
+
+#[derive(Clone, Copy, CandidType, Deserialize)]
+pub enum MemberRole {
+    Viewer,
+    Recruiter,
+    Owner,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct CompanyMember {
+    pub principal: Principal,
+    pub role: MemberRole,
+    pub joined_at: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct CompanyInvite {
+    pub company_id: Principal,
+    pub principal: Principal,
+    pub role: MemberRole,
+    pub invited_by: Principal,
+    pub created_at: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct LedgerConfig {
+    pub canister_id: Principal,
+    pub token_symbol: String,
+    pub fee: u64,
+}
+
+#[derive(Clone, Copy, CandidType, Deserialize)]
+pub enum Payout {
+    Release,
+    Refund,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub enum EscrowStatus {
+    Held,
+    Paying {
+        payout: Payout,
+        to: Principal,
+        created_at_time: u64,
+        error: Option<String>,
+    },
+    Paid {
+        payout: Payout,
+        to: Principal,
+        block_index: Nat,
+    },
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct Escrow {
+    pub job_id: u64,
+    pub amount: u64,
+    pub funded_by: Principal,
+    pub funding_block: Nat,
+    pub fee: Option<u64>,
+    pub status: EscrowStatus,
+    pub updated_at: u64,
+    pub subaccount: Option<[u8; 32]>,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub enum EndorsementKind {
+    Endorsement,
+    Attestation {
+        company_id: Principal,
+        application_id: u64,
+    },
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct Endorsement {
+    pub id: u64,
+    pub applicant_id: Principal,
+    pub skill_id: u16,
+    pub issuer: Principal,
+    pub kind: EndorsementKind,
+    pub created_at: u64,
+}
+
+pub type RoleStore = BTreeMap<Principal, BTreeSet<Role>>;
+pub type AdminLogStore = BTreeMap<u64, AdminLogEntry>;
+pub type CompanyMemberStore = BTreeMap<Principal, BTreeMap<Principal, CompanyMember>>;
+pub type CompanyInviteStore = BTreeMap<Principal, BTreeMap<Principal, CompanyInvite>>;
+pub type EscrowStore = BTreeMap<u64, Escrow>;
+pub type SkillAliasStore = BTreeMap<String, u16>;
+pub type SkillParentStore = BTreeMap<u16, u16>;
+pub type EndorsementStore = BTreeMap<u64, Endorsement>;
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub struct TalentSettings {
+    pub discoverable: bool,
+    pub available_from: u64,
+    pub contact_email: String,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize)]
+pub enum ContactStatus {
+    #[default]
+    Pending,
+    Accepted,
+    Declined,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct ContactRequest {
+    pub id: u64,
+    pub company_id: Principal,
+    pub applicant_id: Principal,
+    pub requested_by: Principal,
+    pub message: String,
+    pub status: ContactStatus,
+    pub contact_email: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct SavedSearch {
+    pub id: u64,
+    pub owner: Principal,
+    pub name: String,
+    pub skill_ids: Vec<u16>,
+    pub min_bounty: Option<u64>,
+    pub keywords: Vec<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub enum NotificationKind {
+    JobAlert { search_id: u64, job_id: u64 },
+    ApplicationReceived { application_id: u64, job_id: u64 },
+    OfferAccepted { application_id: u64, job_id: u64 },
+    OfferDeclined { application_id: u64, job_id: u64 },
+    ApplicationWithdrawn { application_id: u64, job_id: u64 },
+    OfferMade { application_id: u64, job_id: u64 },
+    ApplicationRejected { application_id: u64, job_id: u64 },
+    JobCanceled { job_id: u64 },
+    JobClosed { job_id: u64 },
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct Notification {
+    pub id: u64,
+    pub kind: NotificationKind,
+    pub read: bool,
+    pub created_at: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct Webhook {
+    pub id: u64,
+    pub company_id: Principal,
+    pub url: String,
+    pub secret: String,
+    pub created_by: Principal,
+    pub created_at: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub enum DeliveryStatus {
+    Pending,
+    Failed,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct Delivery {
+    pub id: u64,
+    pub webhook_id: u64,
+    pub company_id: Principal,
+    pub event: String,
+    pub body: String,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+pub type ClosingJobStore = BTreeSet<u64>;
+pub type SkillMergeStore = BTreeMap<u16, u16>;
+pub type TalentStore = BTreeMap<Principal, TalentSettings>;
+pub type ContactRequestStore = BTreeMap<u64, ContactRequest>;
+pub type SavedSearchStore = BTreeMap<u64, SavedSearch>;
+pub type PendingAlertStore = BTreeMap<u64, u64>;
+pub type InboxStore = BTreeMap<Principal, BTreeMap<u64, Notification>>;
+pub type WebhookStore = BTreeMap<u64, Webhook>;
+pub type DeliveryStore = BTreeMap<u64, Delivery>;
+
+#[derive(Default, CandidType, Deserialize)]
+pub struct StableState {
+    pub applicant_profiles: BTreeMap<Principal, ApplicantProfile>,
+    pub company_profiles: BTreeMap<Principal, CompanyProfile>,
+    pub job_id: u64,
+    pub jobs: BTreeMap<u64, Job>,
+    pub application_id: u64,
+    pub applications: BTreeMap<u64, Application>,
+    pub skill_id: u16,
+    pub skills: BTreeMap<u16, Skill>,
+    pub roles: Option<RoleStore>,
+    pub admin_log_id: Option<u64>,
+    pub admin_log: Option<AdminLogStore>,
+    pub company_members: Option<CompanyMemberStore>,
+    pub company_invites: Option<CompanyInviteStore>,
+    pub ledger: Option<LedgerConfig>,
+    pub expired_application_status: Option<ApplicationStatus>,
+    pub closing_jobs: Option<ClosingJobStore>,
+    pub escrows: Option<EscrowStore>,
+    pub skill_aliases: Option<SkillAliasStore>,
+    pub skill_parents: Option<SkillParentStore>,
+    pub skill_merges: Option<SkillMergeStore>,
+    pub endorsement_id: Option<u64>,
+    pub endorsements: Option<EndorsementStore>,
+    pub talent: Option<TalentStore>,
+    pub contact_request_id: Option<u64>,
+    pub contact_requests: Option<ContactRequestStore>,
+    pub saved_search_id: Option<u64>,
+    pub saved_searches: Option<SavedSearchStore>,
+    pub pending_alerts: Option<PendingAlertStore>,
+    pub notification_id: Option<u64>,
+    pub inbox: Option<InboxStore>,
+    pub webhook_id: Option<u64>,
+    pub webhooks: Option<WebhookStore>,
+    pub delivery_id: Option<u64>,
+    pub deliveries: Option<DeliveryStore>,
+}
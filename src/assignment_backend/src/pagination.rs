@@ -0,0 +1,465 @@
+use candid::{CandidType, Deserialize, Principal};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{BoardError, BoardResult};
+
+pub const DEFAULT_PAGE_SIZE: u64 = 20;
+pub const MAX_PAGE_SIZE: u64 = 100;
+
+#[derive(Clone, Copy, Debug, Default, CandidType, Deserialize, PartialEq)]
+pub enum SortDirection {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+// cursor is the key of the first item of the page, as handed out in `next_cursor`.
+// leave it empty to start from the first (or last, when descending) item.
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct PageRequest {
+    pub cursor: Option<String>,
+    pub limit: Option<u64>,
+    pub direction: Option<SortDirection>,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub total: u64,
+}
+
+pub trait CursorKey: Ord + Clone {
+    fn to_cursor(&self) -> String;
+    fn from_cursor(cursor: &str) -> Option<Self>;
+}
+
+impl CursorKey for u64 {
+    fn to_cursor(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_cursor(cursor: &str) -> Option<Self> {
+        cursor.parse().ok()
+    }
+}
+
+impl CursorKey for u16 {
+    fn to_cursor(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_cursor(cursor: &str) -> Option<Self> {
+        cursor.parse().ok()
+    }
+}
+
+impl CursorKey for Principal {
+    fn to_cursor(&self) -> String {
+        self.to_text()
+    }
+
+    fn from_cursor(cursor: &str) -> Option<Self> {
+        Principal::from_text(cursor).ok()
+    }
+}
+
+// the score half of a ranked cursor, see `paginate_ranked`
+pub trait Score: PartialOrd + Copy {
+    fn encode(&self) -> String;
+    fn decode(text: &str) -> Option<Self>;
+}
+
+impl Score for u64 {
+    fn encode(&self) -> String {
+        self.to_string()
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        text.parse().ok()
+    }
+}
+
+// the bits, so the next call finds the exact score again
+impl Score for f64 {
+    fn encode(&self) -> String {
+        self.to_bits().to_string()
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        text.parse().ok().map(f64::from_bits)
+    }
+}
+
+// a score with a tie breaker, compared in that order
+impl<A: Score, B: Score> Score for (A, B) {
+    fn encode(&self) -> String {
+        format!("{}.{}", self.0.encode(), self.1.encode())
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        let (first, second) = text.split_once('.')?;
+        Some((A::decode(first)?, B::decode(second)?))
+    }
+}
+
+// a place in a list ranked best first: the score of a hit and its key, which breaks ties
+#[derive(Clone)]
+struct Rank<S, K>(S, K);
+
+impl<S: Score, K: Ord> Ord for Rank<S, K> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .0
+            .partial_cmp(&self.0)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| self.1.cmp(&other.1))
+    }
+}
+
+impl<S: Score, K: Ord> PartialOrd for Rank<S, K> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S: Score, K: Ord> PartialEq for Rank<S, K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<S: Score, K: Ord> Eq for Rank<S, K> {}
+
+impl<S: Score, K: CursorKey> CursorKey for Rank<S, K> {
+    fn to_cursor(&self) -> String {
+        format!("{}:{}", self.0.encode(), self.1.to_cursor())
+    }
+
+    fn from_cursor(cursor: &str) -> Option<Self> {
+        let (score, key) = cursor.split_once(':')?;
+        Some(Rank(S::decode(score)?, K::from_cursor(key)?))
+    }
+}
+
+impl PageRequest {
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE) as usize
+    }
+
+    pub(crate) fn direction(&self) -> SortDirection {
+        self.direction.unwrap_or_default()
+    }
+
+    pub(crate) fn cursor<K: CursorKey>(&self) -> BoardResult<Option<K>> {
+        match &self.cursor {
+            None => Ok(None),
+            Some(cursor) => K::from_cursor(cursor)
+                .map(Some)
+                .ok_or_else(|| BoardError::validation("cursor", "malformed cursor")),
+        }
+    }
+}
+
+// takes a page off entries that already start at the cursor, in the requested direction.
+// stores that are not a BTreeMap, like the ones in stable memory, page through this.
+pub(crate) fn collect<K: CursorKey, T>(
+    mut entries: impl Iterator<Item = (K, T)>,
+    limit: usize,
+    total: usize,
+) -> Page<T> {
+    let items = entries.by_ref().take(limit).map(|(_, item)| item).collect();
+    let next_cursor = entries.next().map(|(key, _)| key.to_cursor());
+
+    Page {
+        items,
+        next_cursor,
+        total: total as u64,
+    }
+}
+
+pub fn paginate<K: CursorKey, V, T>(
+    map: &BTreeMap<K, V>,
+    request: &PageRequest,
+    mut f: impl FnMut(&V) -> T,
+) -> BoardResult<Page<T>> {
+    let cursor = request.cursor::<K>()?;
+    let limit = request.limit();
+
+    let entries: Box<dyn Iterator<Item = (&K, &V)>> = match (request.direction(), cursor) {
+        (SortDirection::Ascending, None) => Box::new(map.iter()),
+        (SortDirection::Ascending, Some(cursor)) => Box::new(map.range(cursor..)),
+        (SortDirection::Descending, None) => Box::new(map.iter().rev()),
+        (SortDirection::Descending, Some(cursor)) => Box::new(map.range(..=cursor).rev()),
+    };
+
+    Ok(collect(
+        entries.map(|(key, value)| (key.clone(), f(value))),
+        limit,
+        map.len(),
+    ))
+}
+
+// pages over the ids held by an index, `f` resolves an id to its record
+pub fn paginate_ids<T>(
+    ids: &BTreeSet<u64>,
+    request: &PageRequest,
+    mut f: impl FnMut(&u64) -> Option<T>,
+) -> BoardResult<Page<T>> {
+    let cursor = request.cursor::<u64>()?;
+    let limit = request.limit();
+
+    let entries: Box<dyn Iterator<Item = &u64>> = match (request.direction(), cursor) {
+        (SortDirection::Ascending, None) => Box::new(ids.iter()),
+        (SortDirection::Ascending, Some(cursor)) => Box::new(ids.range(cursor..)),
+        (SortDirection::Descending, None) => Box::new(ids.iter().rev()),
+        (SortDirection::Descending, Some(cursor)) => Box::new(ids.range(..=cursor).rev()),
+    };
+
+    Ok(collect(
+        entries.filter_map(|id| f(id).map(|item| (*id, item))),
+        limit,
+        ids.len(),
+    ))
+}
+
+// pages over hits ranked best first, ascending starts at the best one. the cursor holds the
+// score and key of the first hit of the next page, so a page starts where the last one ended
+// even when scores repeat. `f` only builds the hits of the page.
+pub fn paginate_ranked<S: Score, K: CursorKey, T>(
+    ranked: Vec<(S, K)>,
+    request: &PageRequest,
+    mut f: impl FnMut(&S, &K) -> Option<T>,
+) -> BoardResult<Page<T>> {
+    let cursor = request.cursor::<Rank<S, K>>()?;
+    let limit = request.limit();
+
+    let mut ranked: Vec<Rank<S, K>> = ranked
+        .into_iter()
+        .map(|(score, key)| Rank(score, key))
+        .collect();
+    ranked.sort();
+
+    let entries: Box<dyn Iterator<Item = &Rank<S, K>>> = match (request.direction(), cursor) {
+        (SortDirection::Ascending, None) => Box::new(ranked.iter()),
+        (SortDirection::Ascending, Some(cursor)) => {
+            let start = ranked.partition_point(|rank| *rank < cursor);
+            Box::new(ranked[start..].iter())
+        }
+        (SortDirection::Descending, None) => Box::new(ranked.iter().rev()),
+        (SortDirection::Descending, Some(cursor)) => {
+            let end = ranked.partition_point(|rank| *rank <= cursor);
+            Box::new(ranked[..end].iter().rev())
+        }
+    };
+
+    let mut items = Vec::new();
+    let mut next_cursor = None;
+
+    for rank in entries {
+        if items.len() == limit {
+            next_cursor = Some(rank.to_cursor());
+            break;
+        }
+
+        items.extend(f(&rank.0, &rank.1));
+    }
+
+    Ok(Page {
+        items,
+        next_cursor,
+        total: ranked.len() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(cursor: Option<&str>, limit: u64, direction: SortDirection) -> PageRequest {
+        PageRequest {
+            cursor: cursor.map(str::to_string),
+            limit: Some(limit),
+            direction: Some(direction),
+        }
+    }
+
+    fn numbers() -> BTreeMap<u64, u64> {
+        (1..=5).map(|key| (key, key * 10)).collect()
+    }
+
+    #[test]
+    fn collect_hands_out_the_key_of_the_next_page() {
+        let page = collect((1..=5u64).map(|key| (key, key)), 2, 5);
+
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor.as_deref(), Some("3"));
+        assert_eq!(page.total, 5);
+    }
+
+    #[test]
+    fn collect_has_no_cursor_on_the_last_page() {
+        let page = collect((1..=2u64).map(|key| (key, key)), 2, 2);
+
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[test]
+    fn paginate_walks_every_item_once() {
+        let map = numbers();
+        let mut cursor = None;
+        let mut seen = Vec::new();
+
+        loop {
+            let page = paginate(
+                &map,
+                &request(cursor.as_deref(), 2, SortDirection::Ascending),
+                |value| *value,
+            )
+            .unwrap();
+            seen.extend(page.items);
+
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        assert_eq!(seen, vec![10, 20, 30, 40, 50]);
+    }
+
+    #[test]
+    fn paginate_descending_starts_at_the_cursor() {
+        let page = paginate(
+            &numbers(),
+            &request(Some("4"), 2, SortDirection::Descending),
+            |value| *value,
+        )
+        .unwrap();
+
+        assert_eq!(page.items, vec![40, 30]);
+        assert_eq!(page.next_cursor.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn paginate_rejects_a_malformed_cursor() {
+        let result = paginate(
+            &numbers(),
+            &request(Some("three"), 2, SortDirection::Ascending),
+            |value| *value,
+        );
+
+        assert_eq!(
+            result.unwrap_err(),
+            BoardError::validation("cursor", "malformed cursor")
+        );
+    }
+
+    #[test]
+    fn limit_is_clamped() {
+        assert_eq!(request(None, 0, SortDirection::Ascending).limit(), 1);
+        assert_eq!(
+            request(None, 1_000, SortDirection::Ascending).limit(),
+            MAX_PAGE_SIZE as usize
+        );
+        assert_eq!(PageRequest::default().limit(), DEFAULT_PAGE_SIZE as usize);
+    }
+
+    #[test]
+    fn paginate_ids_skips_ids_without_a_record() {
+        let ids: BTreeSet<u64> = (1..=5).collect();
+        let page = paginate_ids(&ids, &request(None, 2, SortDirection::Ascending), |id| {
+            (id % 2 == 1).then_some(*id)
+        })
+        .unwrap();
+
+        assert_eq!(page.items, vec![1, 3]);
+        assert_eq!(page.next_cursor.as_deref(), Some("5"));
+        assert_eq!(page.total, 5);
+    }
+
+    // (score, key), 2 and 4 tie
+    fn ranked() -> Vec<(f64, u64)> {
+        vec![(0.5, 1), (2.0, 2), (3.0, 3), (2.0, 4), (1.0, 5)]
+    }
+
+    #[test]
+    fn paginate_ranked_walks_best_first_with_ties_by_key() {
+        let mut cursor = None;
+        let mut seen = Vec::new();
+
+        loop {
+            let page = paginate_ranked(
+                ranked(),
+                &request(cursor.as_deref(), 2, SortDirection::Ascending),
+                |_, key| Some(*key),
+            )
+            .unwrap();
+            seen.extend(page.items);
+
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        assert_eq!(seen, vec![3, 2, 4, 5, 1]);
+    }
+
+    #[test]
+    fn a_ranked_cursor_survives_changes_around_it() {
+        let first = paginate_ranked(
+            ranked(),
+            &request(None, 2, SortDirection::Ascending),
+            |_, key| Some(*key),
+        )
+        .unwrap();
+        assert_eq!(first.next_cursor, Some(format!("{}:4", 2.0f64.to_bits())));
+
+        // a new best hit and a hit gone from the first page don't shift the next one
+        let mut changed = ranked();
+        changed.retain(|(_, key)| *key != 3);
+        changed.push((9.0, 6));
+
+        let next = paginate_ranked(
+            changed,
+            &request(first.next_cursor.as_deref(), 2, SortDirection::Ascending),
+            |_, key| Some(*key),
+        )
+        .unwrap();
+        assert_eq!(next.items, vec![4, 5]);
+    }
+
+    #[test]
+    fn paginate_ranked_builds_only_the_page() {
+        let mut built = Vec::new();
+        let page = paginate_ranked(
+            vec![((1u64, 2u64), 1u64), ((1, 3), 2), ((2, 0), 3)],
+            &request(Some("1.3:2"), 1, SortDirection::Descending),
+            |_, key| {
+                built.push(*key);
+                Some(*key)
+            },
+        )
+        .unwrap();
+
+        assert_eq!(page.items, vec![2]);
+        assert_eq!(page.next_cursor.as_deref(), Some("2.0:3"));
+        assert_eq!(built, vec![2]);
+    }
+
+    #[test]
+    fn paginate_ranked_rejects_a_malformed_cursor() {
+        let result = paginate_ranked(
+            ranked(),
+            &request(Some("4"), 2, SortDirection::Ascending),
+            |_, key| Some(*key),
+        );
+
+        assert!(result.is_err());
+    }
+}
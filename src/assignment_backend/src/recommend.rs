@@ -0,0 +1,209 @@
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::time;
+use std::collections::BTreeSet;
+
+use crate::{endorsements, index, skills, talent};
+use crate::{ApplicantProfile, ApplicantProfileResponse, ApplicantSkill, Job, JobResponse};
+use crate::{JobSkill, JobStatus, APPLICANT_PROFILE_STORE, APPLICATION_STORE, JOB_STORE};
+
+// a required skill counts this many times as much as a nice to have one
+const REQUIRED_WEIGHT: u64 = 3;
+const OPTIONAL_WEIGHT: u64 = 1;
+
+// points for one matched skill, by how well the held skill meets the asked level and years
+const FULL_FIT: u64 = 10;
+const PARTIAL_FIT: u64 = 6;
+const WEAK_FIT: u64 = 3;
+
+// scores keep their full value for a week, then fade to half over three months
+const DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+const FRESH_FOR: u64 = 7 * DAY;
+const STALE_AFTER: u64 = 90 * DAY;
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct MatchBreakdown {
+    pub score: u64,
+    // endorsement weight of the matching skills, breaks ties between equal scores
+    pub weight: u64,
+    pub matched_skills: Vec<u16>,
+    pub missing_required: Vec<u16>,
+    pub missing_optional: Vec<u16>,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct JobRecommendation {
+    pub job: JobResponse,
+    pub breakdown: MatchBreakdown,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct CandidateRecommendation {
+    pub profile: ApplicantProfileResponse,
+    pub breakdown: MatchBreakdown,
+}
+
+fn fit(wanted: &JobSkill, held: &ApplicantSkill) -> u64 {
+    match (
+        held.proficiency >= wanted.proficiency,
+        held.years >= wanted.years,
+    ) {
+        (true, true) => FULL_FIT,
+        (true, false) | (false, true) => PARTIAL_FIT,
+        (false, false) => WEAK_FIT,
+    }
+}
+
+// percent of the score kept for something last touched at `at`
+fn recency(at: u64) -> u64 {
+    let age = time().saturating_sub(at);
+
+    if age <= FRESH_FOR {
+        100
+    } else if age >= STALE_AFTER {
+        50
+    } else {
+        100 - 50 * (age - FRESH_FOR) / (STALE_AFTER - FRESH_FOR)
+    }
+}
+
+// how well a profile covers the skills of a job. a held skill covers a job skill
+// when it is the same skill or one below it, the best fitting one counts.
+// `active_at` is the job's posting time or the profile's last update, whichever is ranked.
+fn breakdown(
+    applicant_id: &Principal,
+    profile: &ApplicantProfile,
+    job: &Job,
+    active_at: u64,
+) -> MatchBreakdown {
+    let mut breakdown = MatchBreakdown::default();
+    let mut points = 0;
+    let mut held_skills = BTreeSet::new();
+
+    for (skill_id, wanted) in &job.required_skills {
+        let best = profile
+            .skills
+            .iter()
+            .filter(|(held_id, _)| skills::covers(*skill_id, **held_id))
+            .max_by_key(|(_, held)| fit(wanted, held));
+
+        match best {
+            Some((held_id, held)) => {
+                let weight = if wanted.required {
+                    REQUIRED_WEIGHT
+                } else {
+                    OPTIONAL_WEIGHT
+                };
+
+                points += weight * fit(wanted, held);
+                held_skills.insert(*held_id);
+                breakdown.matched_skills.push(*skill_id);
+            }
+            None if wanted.required => breakdown.missing_required.push(*skill_id),
+            None => breakdown.missing_optional.push(*skill_id),
+        }
+    }
+
+    breakdown.score = points * recency(active_at) / 100;
+    breakdown.weight = endorsements::weight(applicant_id, &held_skills);
+
+    breakdown
+}
+
+// open jobs asking for a skill the applicant holds or one above it, with their scores.
+// `job_recommendation` builds the ones on a page.
+pub fn jobs_for(applicant_id: &Principal) -> Vec<((u64, u64), u64)> {
+    let Some(profile) =
+        APPLICANT_PROFILE_STORE.with(|store| store.borrow().get(applicant_id).cloned())
+    else {
+        return Vec::new();
+    };
+
+    let open = index::jobs_by_status(&JobStatus::Open);
+    let ids: BTreeSet<u64> = profile
+        .skills
+        .keys()
+        .flat_map(|skill_id| skills::ancestors(*skill_id).into_iter().chain([*skill_id]))
+        .flat_map(|skill_id| index::jobs_by_skill(&skill_id))
+        .filter(|id| open.contains(id))
+        .collect();
+
+    JOB_STORE.with(|store| {
+        let store = store.borrow();
+
+        ids.iter()
+            .filter_map(|id| store.get(id))
+            .map(|job| {
+                (
+                    breakdown(applicant_id, &profile, job, job.created_at),
+                    job.id,
+                )
+            })
+            .filter(|(breakdown, _)| breakdown.score > 0)
+            .map(|(breakdown, id)| ((breakdown.score, breakdown.weight), id))
+            .collect()
+    })
+}
+
+pub fn job_recommendation(applicant_id: &Principal, job_id: u64) -> Option<JobRecommendation> {
+    let profile =
+        APPLICANT_PROFILE_STORE.with(|store| store.borrow().get(applicant_id).cloned())?;
+
+    JOB_STORE.with(|store| {
+        let store = store.borrow();
+        let job = store.get(&job_id)?;
+
+        Some(JobRecommendation {
+            breakdown: breakdown(applicant_id, &profile, job, job.created_at),
+            job: JobResponse::from(job),
+        })
+    })
+}
+
+// applicants holding a skill of the job, with their scores. only applicants who opted in
+// to talent search or applied to this job are considered. `candidate_recommendation`
+// builds the ones on a page.
+pub fn candidates_for(job: &Job) -> Vec<((u64, u64), Principal)> {
+    let applied: BTreeSet<Principal> = APPLICATION_STORE.with(|store| {
+        let store = store.borrow();
+
+        index::applications_by_job(&job.id)
+            .iter()
+            .filter_map(|id| store.get(id))
+            .filter_map(|application| application.applicant_id)
+            .collect()
+    });
+
+    let ids: BTreeSet<Principal> = job
+        .required_skills
+        .keys()
+        .flat_map(|skill_id| skills::subtree(*skill_id))
+        .flat_map(|skill_id| index::applicants_by_skill(&skill_id))
+        .filter(|id| applied.contains(id) || talent::is_discoverable(id))
+        .collect();
+
+    APPLICANT_PROFILE_STORE.with(|store| {
+        let store = store.borrow();
+
+        ids.iter()
+            .filter_map(|id| store.get(id).map(|profile| (id, profile)))
+            .map(|(id, profile)| (breakdown(id, profile, job, profile.updated_at), *id))
+            .filter(|(breakdown, _)| breakdown.score > 0)
+            .map(|(breakdown, id)| ((breakdown.score, breakdown.weight), id))
+            .collect()
+    })
+}
+
+pub fn candidate_recommendation(
+    job: &Job,
+    applicant_id: &Principal,
+) -> Option<CandidateRecommendation> {
+    APPLICANT_PROFILE_STORE.with(|store| {
+        let store = store.borrow();
+        let profile = store.get(applicant_id)?;
+
+        Some(CandidateRecommendation {
+            breakdown: breakdown(applicant_id, profile, job, profile.updated_at),
+            profile: profile.into(),
+        })
+    })
+}
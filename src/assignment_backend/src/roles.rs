@@ -0,0 +1,164 @@
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::time;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Admin,
+    Moderator,
+    Company,
+    Applicant,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct InitArgs {
+    pub admins: Vec<Principal>,
+    pub ledger: Option<crate::LedgerConfig>,
+    // what pending applications become when their job expires, Rejected unless set
+    pub expired_application_status: Option<crate::ApplicationStatus>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub enum AdminAction {
+    GrantRole {
+        principal: Principal,
+        role: Role,
+    },
+    RevokeRole {
+        principal: Principal,
+        role: Role,
+    },
+    SetJobStatus {
+        job_id: u64,
+        status: crate::JobStatus,
+    },
+    SetApplicationStatus {
+        application_id: u64,
+        status: crate::ApplicationStatus,
+    },
+    UpdateJob {
+        job_id: u64,
+    },
+    UpdateApplicantProfile {
+        principal: Principal,
+    },
+    UpdateCompanyProfile {
+        principal: Principal,
+    },
+    AddSkillAlias {
+        alias: String,
+        skill_id: u16,
+    },
+    RemoveSkillAlias {
+        alias: String,
+    },
+    MergeSkills {
+        from: u16,
+        into: u16,
+    },
+    SetSkillParent {
+        skill_id: u16,
+        parent_id: Option<u16>,
+    },
+    SweepSkills {
+        skill_ids: Vec<u16>,
+    },
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct AdminLogEntry {
+    pub id: u64,
+    pub admin: Principal,
+    pub action: AdminAction,
+    pub reason: String,
+    pub created_at: u64,
+}
+
+pub type RoleStore = BTreeMap<Principal, BTreeSet<Role>>;
+pub type AdminLogStore = BTreeMap<u64, AdminLogEntry>;
+
+thread_local! {
+    pub(crate) static ROLE_STORE: RefCell<RoleStore> = RefCell::default();
+
+    pub(crate) static ADMIN_LOG_ID_STORE: Cell<u64> = const { Cell::new(0) };
+    pub(crate) static ADMIN_LOG_STORE: RefCell<AdminLogStore> = RefCell::default();
+}
+
+pub fn has_role(principal: &Principal, role: Role) -> bool {
+    ROLE_STORE.with(|store| {
+        store
+            .borrow()
+            .get(principal)
+            .is_some_and(|roles| roles.contains(&role))
+    })
+}
+
+// controllers always count as admins, so a board can't lock itself out
+pub fn is_admin(principal: &Principal) -> bool {
+    ic_cdk::api::is_controller(principal) || has_role(principal, Role::Admin)
+}
+
+pub fn is_moderator(principal: &Principal) -> bool {
+    is_admin(principal) || has_role(principal, Role::Moderator)
+}
+
+pub fn roles_of(principal: &Principal) -> Vec<Role> {
+    ROLE_STORE.with(|store| {
+        store
+            .borrow()
+            .get(principal)
+            .map(|roles| roles.iter().cloned().collect())
+            .unwrap_or_default()
+    })
+}
+
+pub fn grant(principal: Principal, role: Role) {
+    ROLE_STORE.with(|store| {
+        store
+            .borrow_mut()
+            .entry(principal)
+            .or_default()
+            .insert(role);
+    });
+}
+
+pub fn revoke(principal: Principal, role: Role) {
+    ROLE_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+
+        if let Some(roles) = store.get_mut(&principal) {
+            roles.remove(&role);
+
+            if roles.is_empty() {
+                store.remove(&principal);
+            }
+        }
+    });
+}
+
+pub fn seed(admins: &[Principal]) {
+    for admin in admins {
+        grant(*admin, Role::Admin);
+    }
+}
+
+pub fn log(admin: Principal, action: AdminAction, reason: String) {
+    let id = ADMIN_LOG_ID_STORE.with(|id| {
+        id.set(id.get() + 1);
+        id.get()
+    });
+
+    ADMIN_LOG_STORE.with(|store| {
+        store.borrow_mut().insert(
+            id,
+            AdminLogEntry {
+                id,
+                admin,
+                action,
+                reason,
+                created_at: time(),
+            },
+        );
+    });
+}
@@ -0,0 +1,309 @@
+use candid::{CandidType, Deserialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{BoardError, BoardResult, Job, JobResponse, JobStatus, JOB_STORE};
+
+pub const MAX_QUERY_LENGTH: usize = 200;
+pub const MAX_QUERY_TERMS: usize = 10;
+// how many index terms one query term may expand to as a prefix
+const MAX_PREFIX_EXPANSIONS: usize = 50;
+
+// a word in the position counts as much as this many words in the description
+const POSITION_WEIGHT: u32 = 2;
+// a prefix match scores less than the whole word
+const PREFIX_PENALTY: f64 = 0.5;
+
+// BM25 parameters
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "in", "is", "it", "of", "on",
+    "or", "that", "the", "to", "we", "with", "you", "your",
+];
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct SearchHit {
+    pub job: JobResponse,
+    pub score: f64,
+}
+
+// the inverted index is derived from JOB_STORE, so it is rebuilt after an upgrade
+thread_local! {
+    // term -> job id -> weighted term frequency
+    static TERM_STORE: RefCell<BTreeMap<String, BTreeMap<u64, u32>>> = RefCell::default();
+    // job id -> weighted number of terms
+    static LENGTH_STORE: RefCell<BTreeMap<u64, u32>> = RefCell::default();
+    static TOTAL_LENGTH: Cell<u64> = const { Cell::new(0) };
+}
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+        .filter(|token| !STOP_WORDS.contains(&token.as_str()))
+        .collect()
+}
+
+fn terms(job: &Job) -> BTreeMap<String, u32> {
+    let mut terms = BTreeMap::<String, u32>::new();
+
+    for token in tokenize(&job.position) {
+        *terms.entry(token).or_default() += POSITION_WEIGHT;
+    }
+
+    for token in tokenize(&job.description) {
+        *terms.entry(token).or_default() += 1;
+    }
+
+    terms
+}
+
+fn add(id: u64, terms: BTreeMap<String, u32>) {
+    let length: u32 = terms.values().sum();
+
+    TERM_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+
+        for (term, frequency) in terms {
+            store.entry(term).or_default().insert(id, frequency);
+        }
+    });
+    LENGTH_STORE.with(|store| store.borrow_mut().insert(id, length));
+    TOTAL_LENGTH.with(|total| total.set(total.get() + length as u64));
+}
+
+fn remove(id: u64, terms: BTreeMap<String, u32>) {
+    TERM_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+
+        for term in terms.keys() {
+            if let Some(postings) = store.get_mut(term) {
+                postings.remove(&id);
+
+                if postings.is_empty() {
+                    store.remove(term);
+                }
+            }
+        }
+    });
+
+    let length = LENGTH_STORE.with(|store| store.borrow_mut().remove(&id));
+    TOTAL_LENGTH.with(|total| total.set(total.get() - length.unwrap_or_default() as u64));
+}
+
+// called for every write to JOB_STORE. only the text of open jobs is indexed,
+// a job leaves the index when it is closed or canceled and comes back on reopen.
+pub fn reindex(previous: Option<&Job>, job: &Job) {
+    let previous = previous.filter(|previous| previous.status == JobStatus::Open);
+
+    if let Some(previous) = previous {
+        if job.status == JobStatus::Open
+            && previous.position == job.position
+            && previous.description == job.description
+        {
+            return;
+        }
+
+        remove(previous.id, terms(previous));
+    }
+
+    if job.status == JobStatus::Open {
+        add(job.id, terms(job));
+    }
+}
+
+pub fn rebuild() {
+    TERM_STORE.with(|store| store.borrow_mut().clear());
+    LENGTH_STORE.with(|store| store.borrow_mut().clear());
+    TOTAL_LENGTH.with(|total| total.set(0));
+
+    JOB_STORE.with(|store| {
+        for job in store.borrow().values() {
+            if job.status == JobStatus::Open {
+                add(job.id, terms(job));
+            }
+        }
+    });
+}
+
+// index terms a query term matches: itself, and the words it is a prefix of
+fn expand(token: &str) -> Vec<(String, f64)> {
+    TERM_STORE.with(|store| {
+        store
+            .borrow()
+            .range(token.to_string()..)
+            .take_while(|(term, _)| term.starts_with(token))
+            .take(MAX_PREFIX_EXPANSIONS)
+            .map(|(term, _)| {
+                let boost = if term == token { 1.0 } else { PREFIX_PENALTY };
+                (term.clone(), boost)
+            })
+            .collect()
+    })
+}
+
+// BM25 scores of the jobs matching the query, best first.
+// `candidates` limits the result to jobs that passed the filters.
+pub fn search(query: &str, candidates: Option<&BTreeSet<u64>>) -> BoardResult<Vec<(u64, f64)>> {
+    if query.chars().count() > MAX_QUERY_LENGTH {
+        return Err(BoardError::validation("query", "is too long"));
+    }
+
+    let mut tokens = tokenize(query);
+    tokens.sort();
+    tokens.dedup();
+
+    if tokens.is_empty() {
+        return Err(BoardError::validation("query", "has no searchable words"));
+    }
+
+    if tokens.len() > MAX_QUERY_TERMS {
+        return Err(BoardError::validation("query", "has too many words"));
+    }
+
+    let documents = LENGTH_STORE.with(|store| store.borrow().len()) as f64;
+    let average_length = TOTAL_LENGTH.with(|total| total.get()) as f64 / documents.max(1.0);
+
+    let mut scores = BTreeMap::<u64, f64>::new();
+
+    for token in tokens {
+        for (term, boost) in expand(&token) {
+            TERM_STORE.with(|store| {
+                let store = store.borrow();
+                let Some(postings) = store.get(&term) else {
+                    return;
+                };
+
+                let frequency = postings.len() as f64;
+                let idf = ((documents - frequency + 0.5) / (frequency + 0.5) + 1.0).ln();
+
+                for (id, tf) in postings {
+                    if candidates.is_some_and(|candidates| !candidates.contains(id)) {
+                        continue;
+                    }
+
+                    let length = LENGTH_STORE
+                        .with(|store| store.borrow().get(id).cloned())
+                        .unwrap_or_default() as f64;
+                    let tf = *tf as f64;
+                    let score =
+                        idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * length / average_length));
+
+                    *scores.entry(*id).or_default() += boost * score;
+                }
+            });
+        }
+    }
+
+    let mut ranked: Vec<(u64, f64)> = scores.into_iter().collect();
+    ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    Ok(ranked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: u64, position: &str, description: &str) -> Job {
+        Job {
+            id,
+            position: position.to_string(),
+            description: description.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn ids(ranked: Vec<(u64, f64)>) -> Vec<u64> {
+        ranked.into_iter().map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn tokenize_splits_on_anything_but_letters_and_digits() {
+        assert_eq!(
+            tokenize("Senior Rust/WASM dev, 5+ years"),
+            vec!["senior", "rust", "wasm", "dev", "5", "years"]
+        );
+    }
+
+    #[test]
+    fn tokenize_drops_stop_words() {
+        assert_eq!(
+            tokenize("The best of the best and you"),
+            vec!["best", "best"]
+        );
+    }
+
+    #[test]
+    fn tokenize_keeps_non_ascii_words() {
+        assert_eq!(
+            tokenize("Développeur Zürich"),
+            vec!["développeur", "zürich"]
+        );
+    }
+
+    #[test]
+    fn tokenize_of_punctuation_is_empty() {
+        assert!(tokenize(" -- !! ").is_empty());
+    }
+
+    #[test]
+    fn a_word_in_the_position_outranks_one_in_the_description() {
+        reindex(None, &job(1, "designer", "knows some rust"));
+        reindex(None, &job(2, "rust engineer", "backend work"));
+
+        assert_eq!(ids(search("rust", None).unwrap()), vec![2, 1]);
+    }
+
+    #[test]
+    fn a_query_term_matches_words_it_is_a_prefix_of() {
+        reindex(None, &job(1, "developer", ""));
+        reindex(None, &job(2, "dev", ""));
+
+        // the whole word scores higher than the prefix match
+        assert_eq!(ids(search("dev", None).unwrap()), vec![2, 1]);
+    }
+
+    #[test]
+    fn reindex_replaces_the_old_text() {
+        let before = job(1, "gardener", "");
+        reindex(None, &before);
+        reindex(Some(&before), &job(1, "plumber", ""));
+
+        assert!(search("gardener", None).unwrap().is_empty());
+        assert_eq!(ids(search("plumber", None).unwrap()), vec![1]);
+    }
+
+    #[test]
+    fn candidates_limit_the_hits() {
+        reindex(None, &job(1, "welder", ""));
+        reindex(None, &job(2, "welder", ""));
+
+        let candidates = BTreeSet::from([2]);
+        assert_eq!(ids(search("welder", Some(&candidates)).unwrap()), vec![2]);
+    }
+
+    #[test]
+    fn queries_without_words_are_rejected() {
+        assert!(search("the and of", None).is_err());
+        assert!(search(&"a".repeat(MAX_QUERY_LENGTH + 1), None).is_err());
+    }
+
+    #[test]
+    fn only_open_jobs_are_indexed() {
+        let open = job(1, "roofer", "");
+        reindex(None, &open);
+
+        let closed = Job {
+            status: JobStatus::Closed,
+            ..open.clone()
+        };
+        reindex(Some(&open), &closed);
+        assert!(search("roofer", None).unwrap().is_empty());
+
+        reindex(Some(&closed), &open);
+        assert_eq!(ids(search("roofer", None).unwrap()), vec![1]);
+    }
+}
@@ -0,0 +1,607 @@
+use candid::{CandidType, Deserialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::audit::{self, AuditAction, AuditEntity};
+use crate::{alerts, endorsements, index};
+use crate::{ApplicantSkill, BoardError, BoardResult, JobSkill, Skill};
+use crate::{APPLICANT_PROFILE_STORE, JOB_STORE, SKILL_ID_STORE, SKILL_STORE};
+
+// names arrive from any caller, so both the name and the list are bounded
+pub const MAX_SKILL_NAME_LENGTH: usize = 50;
+pub const MAX_SKILLS_PER_REQUEST: usize = 50;
+pub const MAX_YEARS: u8 = 60;
+
+// a skill in the taxonomy, categories are skills that other skills hang under
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct SkillNode {
+    pub skill: Skill,
+    pub parent_id: Option<u16>,
+    pub child_ids: Vec<u16>,
+}
+
+// how many records hold a skill, read from the job and applicant indexes
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct SkillUsage {
+    pub skill: Skill,
+    pub jobs: u64,
+    pub applicants: u64,
+}
+
+// normalized alias -> skill id, maintained by admins
+pub type SkillAliasStore = BTreeMap<String, u16>;
+// skill id -> parent skill id, skills without an entry are roots
+pub type SkillParentStore = BTreeMap<u16, u16>;
+// merged skill id -> the skill it lives on as, for records that keep the old id
+pub type SkillMergeStore = BTreeMap<u16, u16>;
+
+thread_local! {
+    pub(crate) static SKILL_ALIAS_STORE: RefCell<SkillAliasStore> = RefCell::default();
+    pub(crate) static SKILL_PARENT_STORE: RefCell<SkillParentStore> = RefCell::default();
+    pub(crate) static SKILL_MERGE_STORE: RefCell<SkillMergeStore> = RefCell::default();
+
+    // normalized name -> skill id, derived from SKILL_STORE
+    static SKILL_BY_NAME_STORE: RefCell<BTreeMap<String, u16>> = RefCell::default();
+    // parent skill id -> child skill ids, derived from SKILL_PARENT_STORE
+    static SKILL_CHILDREN_STORE: RefCell<BTreeMap<u16, BTreeSet<u16>>> = RefCell::default();
+    // skill id -> calls that resolved it and still wait on the ledger, `sweep` leaves these
+    static IN_FLIGHT_STORE: RefCell<BTreeMap<u16, u32>> = RefCell::default();
+}
+
+// "  Rust   Lang " and "rust lang" are the same skill
+pub fn normalize(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn validate_name(name: &str) -> BoardResult<()> {
+    if name.is_empty() {
+        return Err(BoardError::validation("skills", "skill name is empty"));
+    }
+
+    if name.chars().count() > MAX_SKILL_NAME_LENGTH {
+        return Err(BoardError::validation("skills", "skill name is too long"));
+    }
+
+    Ok(())
+}
+
+pub fn rebuild() {
+    let names = SKILL_STORE.with(|store| {
+        store
+            .borrow()
+            .iter()
+            .map(|(id, skill)| (normalize(&skill.name), *id))
+            .collect()
+    });
+
+    SKILL_BY_NAME_STORE.with(|store| store.replace(names));
+
+    let mut children = BTreeMap::<u16, BTreeSet<u16>>::new();
+    SKILL_PARENT_STORE.with(|store| {
+        for (child, parent) in store.borrow().iter() {
+            children.entry(*parent).or_default().insert(*child);
+        }
+    });
+
+    SKILL_CHILDREN_STORE.with(|store| store.replace(children));
+}
+
+pub fn get(id: u16) -> Option<Skill> {
+    SKILL_STORE.with(|store| store.borrow().get(&id).cloned())
+}
+
+// looks a name up by the canonical names first, then by the aliases
+pub fn find(name: &str) -> Option<Skill> {
+    let name = normalize(name);
+
+    SKILL_BY_NAME_STORE
+        .with(|store| store.borrow().get(&name).cloned())
+        .or_else(|| SKILL_ALIAS_STORE.with(|store| store.borrow().get(&name).cloned()))
+        .and_then(get)
+}
+
+pub fn aliases_of(id: u16) -> Vec<String> {
+    SKILL_ALIAS_STORE.with(|store| {
+        store
+            .borrow()
+            .iter()
+            .filter(|(_, skill_id)| **skill_id == id)
+            .map(|(alias, _)| alias.clone())
+            .collect()
+    })
+}
+
+fn create(name: String) -> BoardResult<Skill> {
+    let id = SKILL_ID_STORE
+        .with(|id| {
+            let next = id.get().checked_add(1)?;
+            id.set(next);
+            Some(next)
+        })
+        .ok_or_else(|| BoardError::conflict("Skill catalog is full"))?;
+
+    let skill = Skill {
+        id: Some(id),
+        name: name.clone(),
+    };
+
+    SKILL_STORE.with(|store| store.borrow_mut().insert(id, skill.clone()));
+    SKILL_BY_NAME_STORE.with(|store| store.borrow_mut().insert(name, id));
+    audit::record_change(AuditEntity::Skill(id), AuditAction::Created);
+
+    Ok(skill)
+}
+
+// a record pointing at a catalog skill, as held by profiles and jobs
+pub trait SkillEntry {
+    fn skill(&self) -> &Skill;
+    fn skill_mut(&mut self) -> &mut Skill;
+    fn years(&self) -> u8;
+}
+
+impl SkillEntry for ApplicantSkill {
+    fn skill(&self) -> &Skill {
+        &self.skill
+    }
+
+    fn skill_mut(&mut self) -> &mut Skill {
+        &mut self.skill
+    }
+
+    fn years(&self) -> u8 {
+        self.years
+    }
+}
+
+impl SkillEntry for JobSkill {
+    fn skill(&self) -> &Skill {
+        &self.skill
+    }
+
+    fn skill_mut(&mut self) -> &mut Skill {
+        &mut self.skill
+    }
+
+    fn years(&self) -> u8 {
+        self.years
+    }
+}
+
+// checks the skills sent by a client without creating any
+fn validate<T: SkillEntry>(entries: &[T]) -> BoardResult<()> {
+    if entries.len() > MAX_SKILLS_PER_REQUEST {
+        return Err(BoardError::validation("skills", "too many skills"));
+    }
+
+    for entry in entries.iter() {
+        if entry.years() > MAX_YEARS {
+            return Err(BoardError::validation("years", "is more than a career"));
+        }
+
+        match entry.skill().id {
+            Some(id) if get(id).is_none() => {
+                return Err(BoardError::not_found("skill", id));
+            }
+            Some(_) => {}
+            None => validate_name(&normalize(&entry.skill().name))?,
+        }
+    }
+
+    Ok(())
+}
+
+// turns the skills sent by a client into catalog entries.
+// an id has to exist, a name is matched against the catalog and only added when it is new.
+// everything is validated before the first skill is created, so a failed call leaves no trace.
+pub fn resolve<T: SkillEntry>(entries: Vec<T>) -> BoardResult<BTreeMap<u16, T>> {
+    validate(&entries)?;
+
+    let mut resolved = BTreeMap::new();
+
+    for mut entry in entries {
+        let skill = match entry.skill().id {
+            Some(id) => get(id).ok_or_else(|| BoardError::not_found("skill", id))?,
+            None => match find(&entry.skill().name) {
+                Some(existing) => existing,
+                None => create(normalize(&entry.skill().name))?,
+            },
+        };
+
+        if let Some(id) = skill.id {
+            *entry.skill_mut() = skill;
+            resolved.insert(id, entry);
+        }
+    }
+
+    Ok(resolved)
+}
+
+// a merge while the call waited moved the skills, like it moves those of stored records
+pub fn refresh<T: SkillEntry>(entries: BTreeMap<u16, T>) -> BTreeMap<u16, T> {
+    let mut refreshed = BTreeMap::new();
+
+    for (id, mut entry) in entries {
+        let id = current(id);
+
+        if let Some(skill) = get(id) {
+            *entry.skill_mut() = skill;
+            refreshed.entry(id).or_insert(entry);
+        }
+    }
+
+    refreshed
+}
+
+// keeps skills a call resolved out of `sweep` until the call hands them back with `release`
+pub fn hold(ids: impl IntoIterator<Item = u16>) {
+    IN_FLIGHT_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+
+        for id in ids {
+            *store.entry(id).or_default() += 1;
+        }
+    });
+}
+
+pub fn release(ids: impl IntoIterator<Item = u16>) {
+    IN_FLIGHT_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+
+        for id in ids {
+            if let Some(calls) = store.get_mut(&id) {
+                *calls -= 1;
+                if *calls == 0 {
+                    store.remove(&id);
+                }
+            }
+        }
+    });
+}
+
+fn in_flight(id: &u16) -> bool {
+    IN_FLIGHT_STORE.with(|store| store.borrow().contains_key(id))
+}
+
+pub fn add_alias(alias: &str, id: u16) -> BoardResult<Skill> {
+    let alias = normalize(alias);
+    validate_name(&alias)?;
+
+    let skill = get(id).ok_or_else(|| BoardError::not_found("skill", id))?;
+
+    if let Some(existing) = find(&alias) {
+        if existing.id != Some(id) {
+            return Err(BoardError::conflict(
+                "Alias already names another skill, merge the skills instead",
+            ));
+        }
+    }
+
+    SKILL_ALIAS_STORE.with(|store| store.borrow_mut().insert(alias, id));
+    audit::record_change(AuditEntity::Skill(id), AuditAction::Updated);
+
+    Ok(skill)
+}
+
+pub fn remove_alias(alias: &str) -> BoardResult<()> {
+    let id = SKILL_ALIAS_STORE
+        .with(|store| store.borrow_mut().remove(&normalize(alias)))
+        .ok_or_else(|| BoardError::not_found("alias", alias))?;
+
+    audit::record_change(AuditEntity::Skill(id), AuditAction::Updated);
+
+    Ok(())
+}
+
+// folds `from` into `into`: jobs and profiles holding `from` hold `into` instead,
+// and the name and aliases of `from` keep resolving, to `into`.
+pub fn merge(from: u16, into: u16) -> BoardResult<Skill> {
+    if from == into {
+        return Err(BoardError::validation(
+            "from",
+            "can't merge a skill into itself",
+        ));
+    }
+
+    let from_skill = get(from).ok_or_else(|| BoardError::not_found("skill", from))?;
+    let into_skill = get(into).ok_or_else(|| BoardError::not_found("skill", into))?;
+
+    let jobs: Vec<_> = JOB_STORE.with(|store| {
+        let store = store.borrow();
+
+        index::jobs_by_skill(&from)
+            .iter()
+            .filter_map(|id| store.get(id).cloned())
+            .collect()
+    });
+
+    // a record holding both keeps what it said about `into`
+    for mut job in jobs {
+        if let Some(mut entry) = job.required_skills.remove(&from) {
+            entry.skill = into_skill.clone();
+            job.required_skills.entry(into).or_insert(entry);
+        }
+        index::insert_job(job);
+    }
+
+    let profiles: Vec<_> = APPLICANT_PROFILE_STORE.with(|store| {
+        let store = store.borrow();
+
+        index::applicants_by_skill(&from)
+            .into_iter()
+            .filter_map(|id| store.get(&id).cloned().map(|profile| (id, profile)))
+            .collect()
+    });
+
+    for (id, mut profile) in profiles {
+        if let Some(mut entry) = profile.skills.remove(&from) {
+            entry.skill = into_skill.clone();
+            profile.skills.entry(into).or_insert(entry);
+        }
+        index::insert_applicant(id, profile);
+    }
+
+    // `into` takes the place of `from` when it sat below it, the children of `from` move under `into`
+    if is_ancestor(from, into) {
+        link(into, parent_of(from));
+    }
+    for child in children_of(from) {
+        if child != into {
+            link(child, Some(into));
+        }
+    }
+    link(from, None);
+
+    // endorsements are never rewritten, they keep `from` and are read through this
+    SKILL_MERGE_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+
+        for skill_id in store.values_mut() {
+            if *skill_id == from {
+                *skill_id = into;
+            }
+        }
+
+        store.insert(from, into);
+    });
+    alerts::retarget(from, into);
+
+    SKILL_STORE.with(|store| store.borrow_mut().remove(&from));
+    SKILL_BY_NAME_STORE.with(|store| store.borrow_mut().remove(&normalize(&from_skill.name)));
+
+    SKILL_ALIAS_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+
+        for skill_id in store.values_mut() {
+            if *skill_id == from {
+                *skill_id = into;
+            }
+        }
+
+        store.insert(normalize(&from_skill.name), into);
+    });
+
+    audit::record_change(AuditEntity::Skill(from), AuditAction::Deleted);
+    audit::record_change(AuditEntity::Skill(into), AuditAction::Updated);
+
+    Ok(into_skill)
+}
+
+// the skill a possibly merged skill id stands for today
+pub fn current(id: u16) -> u16 {
+    SKILL_MERGE_STORE
+        .with(|store| store.borrow().get(&id).cloned())
+        .unwrap_or(id)
+}
+
+// USAGE
+
+pub fn usage(id: u16) -> Option<SkillUsage> {
+    get(id).map(|skill| SkillUsage {
+        skill,
+        jobs: index::job_count_by_skill(&id),
+        applicants: index::applicant_count_by_skill(&id),
+    })
+}
+
+// most used first, ties broken by the lower id
+pub fn popular(limit: usize) -> Vec<SkillUsage> {
+    let ids: Vec<u16> = SKILL_STORE.with(|store| store.borrow().keys().cloned().collect());
+
+    let mut usages: Vec<SkillUsage> = ids.into_iter().filter_map(usage).collect();
+    usages.sort_by_key(|usage| std::cmp::Reverse(usage.jobs + usage.applicants));
+    usages.truncate(limit);
+
+    usages
+}
+
+// removes the skills nothing points at: no job, profile, endorsement, saved search or child skill.
+// typos and skills left behind by failed calls end up here. only the admin `sweep_skills` runs it.
+// skills held by calls still waiting on the ledger are kept, their job doesn't exist yet.
+pub fn sweep() -> Vec<Skill> {
+    let endorsed = endorsements::skills_in_use();
+    let searched = alerts::skills_in_use();
+
+    let orphans: Vec<Skill> = SKILL_STORE.with(|store| {
+        store
+            .borrow()
+            .iter()
+            .filter(|(id, _)| {
+                index::job_count_by_skill(id) == 0
+                    && index::applicant_count_by_skill(id) == 0
+                    && !endorsed.contains(id)
+                    && !searched.contains(id)
+                    && children_of(**id).is_empty()
+                    && !in_flight(id)
+            })
+            .map(|(_, skill)| skill.clone())
+            .collect()
+    });
+
+    for skill in orphans.iter() {
+        let Some(id) = skill.id else {
+            continue;
+        };
+
+        link(id, None);
+        SKILL_STORE.with(|store| store.borrow_mut().remove(&id));
+        SKILL_BY_NAME_STORE.with(|store| store.borrow_mut().remove(&normalize(&skill.name)));
+        SKILL_ALIAS_STORE.with(|store| store.borrow_mut().retain(|_, skill_id| *skill_id != id));
+        audit::record_change(AuditEntity::Skill(id), AuditAction::Deleted);
+    }
+
+    orphans
+}
+
+// TAXONOMY
+
+pub fn parent_of(id: u16) -> Option<u16> {
+    SKILL_PARENT_STORE.with(|store| store.borrow().get(&id).cloned())
+}
+
+pub fn children_of(id: u16) -> BTreeSet<u16> {
+    SKILL_CHILDREN_STORE.with(|store| store.borrow().get(&id).cloned().unwrap_or_default())
+}
+
+pub fn roots() -> BTreeSet<u16> {
+    SKILL_STORE.with(|store| {
+        store
+            .borrow()
+            .keys()
+            .filter(|id| parent_of(**id).is_none())
+            .cloned()
+            .collect()
+    })
+}
+
+// nearest first, the root last
+pub fn ancestors(id: u16) -> Vec<u16> {
+    let mut ancestors = Vec::new();
+    let mut current = parent_of(id);
+
+    while let Some(parent) = current {
+        ancestors.push(parent);
+        current = parent_of(parent);
+    }
+
+    ancestors
+}
+
+fn is_ancestor(ancestor: u16, id: u16) -> bool {
+    ancestors(id).contains(&ancestor)
+}
+
+// the skill itself and everything below it
+pub fn subtree(id: u16) -> BTreeSet<u16> {
+    let mut subtree = BTreeSet::from([id]);
+    let mut pending = vec![id];
+
+    while let Some(current) = pending.pop() {
+        for child in children_of(current) {
+            if subtree.insert(child) {
+                pending.push(child);
+            }
+        }
+    }
+
+    subtree
+}
+
+// a required skill is covered by itself or by anything below it,
+// so "systems programming" is covered by "rust"
+pub fn covers(required: u16, held: u16) -> bool {
+    held == required || is_ancestor(required, held)
+}
+
+pub fn node(id: u16) -> Option<SkillNode> {
+    get(id).map(|skill| SkillNode {
+        skill,
+        parent_id: parent_of(id),
+        child_ids: children_of(id).into_iter().collect(),
+    })
+}
+
+fn link(id: u16, parent: Option<u16>) {
+    let previous = SKILL_PARENT_STORE.with(|store| match parent {
+        Some(parent) => store.borrow_mut().insert(id, parent),
+        None => store.borrow_mut().remove(&id),
+    });
+
+    SKILL_CHILDREN_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+
+        if let Some(previous) = previous {
+            if let Some(children) = store.get_mut(&previous) {
+                children.remove(&id);
+
+                if children.is_empty() {
+                    store.remove(&previous);
+                }
+            }
+        }
+
+        if let Some(parent) = parent {
+            store.entry(parent).or_default().insert(id);
+        }
+    });
+}
+
+pub fn set_parent(id: u16, parent: Option<u16>) -> BoardResult<SkillNode> {
+    get(id).ok_or_else(|| BoardError::not_found("skill", id))?;
+
+    if let Some(parent) = parent {
+        get(parent).ok_or_else(|| BoardError::not_found("skill", parent))?;
+
+        if parent == id || is_ancestor(id, parent) {
+            return Err(BoardError::validation(
+                "parent_id",
+                "a skill can't be placed under itself",
+            ));
+        }
+    }
+
+    link(id, parent);
+    audit::record_change(AuditEntity::Skill(id), AuditAction::Updated);
+
+    node(id).ok_or_else(|| BoardError::not_found("skill", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_folds_case_and_whitespace() {
+        assert_eq!(normalize("  Rust   Lang "), "rust lang");
+        assert_eq!(normalize("rust lang"), "rust lang");
+        assert_eq!(normalize("C++\tDev\n"), "c++ dev");
+    }
+
+    #[test]
+    fn normalize_keeps_an_empty_name_empty() {
+        assert_eq!(normalize("   "), "");
+        assert!(validate_name(&normalize("   ")).is_err());
+    }
+
+    #[test]
+    fn sweep_keeps_skills_held_by_a_call() {
+        let skill = Skill {
+            id: Some(1),
+            name: "rust".to_string(),
+        };
+        SKILL_STORE.with(|store| store.borrow_mut().insert(1, skill));
+
+        hold([1]);
+        hold([1]);
+        assert!(sweep().is_empty());
+
+        release([1]);
+        assert!(sweep().is_empty());
+        assert!(get(1).is_some());
+
+        release([1]);
+        assert!(!in_flight(&1));
+    }
+}
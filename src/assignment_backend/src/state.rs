@@ -11,6 +11,7 @@ pub struct CompanyProfile {
     pub twitter: String,
     pub website: String,
     pub created_at: u64,
+    pub updated_at: u64,
 }
 
 #[derive(Clone, Debug, Default, CandidType, Deserialize)]
@@ -21,6 +22,14 @@ pub struct CompanyParams {
     pub website: String,
 }
 
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct UpdateCompanyParams {
+    pub name: Option<String>,
+    pub logo: Option<String>,
+    pub twitter: Option<String>,
+    pub website: Option<String>,
+}
+
 #[derive(Clone, Debug, Default, CandidType, Deserialize)]
 pub struct ApplicantProfile {
     pub id: Option<Principal>,
@@ -29,9 +38,10 @@ pub struct ApplicantProfile {
     pub nickname: String,
     pub bio: String,
     pub created_at: u64,
+    pub updated_at: u64,
 
     // how to represent BTreeMap in candid?
-    pub skills: BTreeMap<u16, Skill>,
+    pub skills: BTreeMap<u16, ApplicantSkill>,
 }
 
 #[derive(Clone, Debug, Default, CandidType, Deserialize)]
@@ -42,7 +52,23 @@ pub struct ApplicantProfileResponse {
     pub nickname: String,
     pub bio: String,
     pub created_at: u64,
-    pub skills: Vec<Skill>,
+    pub updated_at: u64,
+    pub skills: Vec<ApplicantSkill>,
+}
+
+impl From<&ApplicantProfile> for ApplicantProfileResponse {
+    fn from(profile: &ApplicantProfile) -> Self {
+        Self {
+            id: profile.id,
+            first_name: profile.first_name.clone(),
+            last_name: profile.last_name.clone(),
+            nickname: profile.nickname.clone(),
+            bio: profile.bio.clone(),
+            created_at: profile.created_at,
+            updated_at: profile.updated_at,
+            skills: profile.skills.values().cloned().collect(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, CandidType, Deserialize)]
@@ -53,12 +79,47 @@ pub struct ApplicantParams {
     pub bio: String,
 }
 
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct UpdateApplicantParams {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub nickname: Option<String>,
+    pub bio: Option<String>,
+    pub skills: Option<Vec<ApplicantSkill>>,
+}
+
 #[derive(Clone, Debug, Default, CandidType, Deserialize)]
 pub struct Skill {
     pub id: Option<u16>,
     pub name: String,
 }
 
+#[derive(Clone, Copy, Debug, Default, CandidType, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Proficiency {
+    #[default]
+    Beginner,
+    Intermediate,
+    Advanced,
+    Expert,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct ApplicantSkill {
+    pub skill: Skill,
+    pub proficiency: Proficiency,
+    pub years: u8,
+}
+
+// `proficiency` and `years` are the minimum the job asks for,
+// skills that are not `required` are nice to have
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct JobSkill {
+    pub skill: Skill,
+    pub proficiency: Proficiency,
+    pub years: u8,
+    pub required: bool,
+}
+
 #[derive(Clone, Default, CandidType, Deserialize)]
 pub struct Job {
     pub id: u64,
@@ -67,9 +128,15 @@ pub struct Job {
     pub description: String,
     pub bounty: u64,
     pub status: JobStatus,
+    pub created_at: u64,
+    pub updated_at: u64,
+    // an open job closes on its own at `expires_at`, and records when it did in `expired_at`.
+    // both are opt, so jobs saved before they existed still decode.
+    pub expires_at: Option<u64>,
+    pub expired_at: Option<u64>,
 
     // how to represent BTreeMap in candid?
-    pub required_skills: BTreeMap<u16, Skill>,
+    pub required_skills: BTreeMap<u16, JobSkill>,
 }
 
 #[derive(Clone, Default, CandidType, Deserialize)]
@@ -80,7 +147,29 @@ pub struct JobResponse {
     pub description: String,
     pub bounty: u64,
     pub status: JobStatus,
-    pub required_skills: Vec<Skill>,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub expires_at: Option<u64>,
+    pub expired_at: Option<u64>,
+    pub required_skills: Vec<JobSkill>,
+}
+
+impl From<&Job> for JobResponse {
+    fn from(job: &Job) -> Self {
+        Self {
+            id: job.id,
+            company_id: job.company_id,
+            position: job.position.clone(),
+            description: job.description.clone(),
+            bounty: job.bounty,
+            status: job.status.clone(),
+            created_at: job.created_at,
+            updated_at: job.updated_at,
+            expires_at: job.expires_at,
+            expired_at: job.expired_at,
+            required_skills: job.required_skills.values().cloned().collect(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, CandidType, Deserialize)]
@@ -88,6 +177,15 @@ pub struct JobParams {
     pub position: String,
     pub description: String,
     pub bounty: u64,
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct UpdateJobParams {
+    pub position: Option<String>,
+    pub description: Option<String>,
+    pub bounty: Option<u64>,
+    pub required_skills: Option<Vec<JobSkill>>,
 }
 
 #[derive(Clone, Debug, Default, CandidType, Deserialize)]
@@ -99,6 +197,10 @@ pub struct Application {
     pub status: ApplicationStatus,
     pub salary_from: u64,
     pub salary_to: u64,
+    pub updated_at: u64,
+
+    // receives the bounty instead of the applicant when set
+    pub referrer: Option<Principal>,
 }
 
 #[derive(Clone, Debug, Default, CandidType, Deserialize)]
@@ -107,17 +209,27 @@ pub struct ApplicationParams {
     pub contact_email: String,
     pub salary_from: u64,
     pub salary_to: u64,
+    pub referrer: Option<Principal>,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct UpdateApplicationParams {
+    pub contact_email: Option<String>,
+    pub salary_from: Option<u64>,
+    pub salary_to: Option<u64>,
 }
 
-#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Default, CandidType, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum JobStatus {
+    #[default]
     Open,
     Closed,
     Canceled,
 }
 
-#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Default, CandidType, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ApplicationStatus {
+    #[default]
     Applied,
     Withdraw,
     Offer,
@@ -125,14 +237,27 @@ pub enum ApplicationStatus {
     Rejected,
 }
 
-impl Default for JobStatus {
-    fn default() -> Self {
-        Self::Open
-    }
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct StateSnapshot {
+    pub version: u32,
+    pub data: Vec<u8>,
 }
 
-impl Default for ApplicationStatus {
-    fn default() -> Self {
-        Self::Applied
-    }
+// an export started with start_state_export, read back with export_state_chunk
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct StateExport {
+    pub version: u32,
+    pub size: u64,
+    pub chunk_size: u64,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct MigrationReport {
+    pub from_version: u32,
+    pub to_version: u32,
+    pub applicant_profiles: u64,
+    pub company_profiles: u64,
+    pub jobs: u64,
+    pub applications: u64,
+    pub skills: u64,
 }
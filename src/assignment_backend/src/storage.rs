@@ -0,0 +1,382 @@
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::reader::Reader;
+use ic_stable_structures::writer::Writer;
+use ic_stable_structures::{DefaultMemoryImpl, Memory as _};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use crate::alerts::{PendingAlertStore, SavedSearchStore};
+use crate::alerts::{PENDING_ALERT_STORE, SAVED_SEARCH_ID_STORE, SAVED_SEARCH_STORE};
+use crate::endorsements::{EndorsementStore, ENDORSEMENT_ID_STORE, ENDORSEMENT_STORE};
+use crate::escrow::{EscrowStore, LedgerConfig, ESCROW_STORE, LEDGER_CONFIG};
+use crate::expiry::{ClosingJobStore, CLOSING_JOB_STORE, EXPIRED_APPLICATION_STATUS};
+use crate::inbox::{InboxStore, INBOX_STORE, NOTIFICATION_ID_STORE};
+use crate::migration::{self, STATE_VERSION};
+use crate::roles::{AdminLogStore, Role, RoleStore};
+use crate::roles::{ADMIN_LOG_ID_STORE, ADMIN_LOG_STORE, ROLE_STORE};
+use crate::skills::{SkillAliasStore, SkillMergeStore, SkillParentStore};
+use crate::skills::{SKILL_ALIAS_STORE, SKILL_MERGE_STORE, SKILL_PARENT_STORE};
+use crate::talent::{ContactRequestStore, TalentStore};
+use crate::talent::{CONTACT_REQUEST_ID_STORE, CONTACT_REQUEST_STORE, TALENT_STORE};
+use crate::teams::{CompanyInviteStore, CompanyMember, CompanyMemberStore, MemberRole};
+use crate::teams::{COMPANY_INVITE_STORE, COMPANY_MEMBER_STORE};
+use crate::webhooks::{
+    DeliveryStore, WebhookStore, DELIVERY_ID_STORE, DELIVERY_STORE, WEBHOOK_ID_STORE, WEBHOOK_STORE,
+};
+use crate::{ApplicantProfileStore, ApplicationStore, CompanyProfileStore, JobStore, SkillStore};
+use crate::{ApplicationStatus, BoardError, BoardResult, StateExport, StateSnapshot};
+use crate::{
+    APPLICANT_PROFILE_STORE, APPLICATION_ID_STORE, APPLICATION_STORE, COMPANY_PROFILE_STORE,
+    JOB_ID_STORE, JOB_STORE, SKILL_ID_STORE, SKILL_STORE,
+};
+
+pub type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+// heap stores are serialized into this region on every upgrade,
+// other regions are free for structures that live in stable memory directly.
+const UPGRADES_MEMORY_ID: MemoryId = MemoryId::new(0);
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+}
+
+pub fn get_memory(id: MemoryId) -> Memory {
+    MEMORY_MANAGER.with(|manager| manager.borrow().get(id))
+}
+
+// everything the canister needs to survive an upgrade,
+// the id counters are kept so ids keep going instead of restarting at zero.
+// secondary indexes are left out, they are rebuilt from the stores after loading.
+// stores added after v2 are `opt`, so older snapshots still decode without a migration.
+#[derive(Default, CandidType, Deserialize)]
+pub struct StableState {
+    pub applicant_profiles: ApplicantProfileStore,
+    pub company_profiles: CompanyProfileStore,
+
+    pub job_id: u64,
+    pub jobs: JobStore,
+
+    pub application_id: u64,
+    pub applications: ApplicationStore,
+
+    pub skill_id: u16,
+    pub skills: SkillStore,
+
+    pub roles: Option<RoleStore>,
+    pub admin_log_id: Option<u64>,
+    pub admin_log: Option<AdminLogStore>,
+
+    pub company_members: Option<CompanyMemberStore>,
+    pub company_invites: Option<CompanyInviteStore>,
+
+    pub ledger: Option<LedgerConfig>,
+    pub expired_application_status: Option<ApplicationStatus>,
+    pub closing_jobs: Option<ClosingJobStore>,
+    pub escrows: Option<EscrowStore>,
+
+    pub skill_aliases: Option<SkillAliasStore>,
+    pub skill_parents: Option<SkillParentStore>,
+    pub skill_merges: Option<SkillMergeStore>,
+
+    pub endorsement_id: Option<u64>,
+    pub endorsements: Option<EndorsementStore>,
+
+    pub talent: Option<TalentStore>,
+    pub contact_request_id: Option<u64>,
+    pub contact_requests: Option<ContactRequestStore>,
+
+    pub saved_search_id: Option<u64>,
+    pub saved_searches: Option<SavedSearchStore>,
+    pub pending_alerts: Option<PendingAlertStore>,
+    pub notification_id: Option<u64>,
+    pub inbox: Option<InboxStore>,
+
+    pub webhook_id: Option<u64>,
+    pub webhooks: Option<WebhookStore>,
+    pub delivery_id: Option<u64>,
+    pub deliveries: Option<DeliveryStore>,
+}
+
+impl StableState {
+    pub fn take() -> Self {
+        Self {
+            applicant_profiles: APPLICANT_PROFILE_STORE.with(|store| store.take()),
+            company_profiles: COMPANY_PROFILE_STORE.with(|store| store.take()),
+            job_id: JOB_ID_STORE.with(|id| id.get()),
+            jobs: JOB_STORE.with(|store| store.take()),
+            application_id: APPLICATION_ID_STORE.with(|id| id.get()),
+            applications: APPLICATION_STORE.with(|store| store.take()),
+            skill_id: SKILL_ID_STORE.with(|id| id.get()),
+            skills: SKILL_STORE.with(|store| store.take()),
+            roles: Some(ROLE_STORE.with(|store| store.take())),
+            admin_log_id: Some(ADMIN_LOG_ID_STORE.with(|id| id.get())),
+            admin_log: Some(ADMIN_LOG_STORE.with(|store| store.take())),
+            company_members: Some(COMPANY_MEMBER_STORE.with(|store| store.take())),
+            company_invites: Some(COMPANY_INVITE_STORE.with(|store| store.take())),
+            ledger: LEDGER_CONFIG.with(|ledger| ledger.take()),
+            expired_application_status: EXPIRED_APPLICATION_STATUS.with(|status| status.take()),
+            closing_jobs: Some(CLOSING_JOB_STORE.with(|store| store.take())),
+            escrows: Some(ESCROW_STORE.with(|store| store.take())),
+            skill_aliases: Some(SKILL_ALIAS_STORE.with(|store| store.take())),
+            skill_parents: Some(SKILL_PARENT_STORE.with(|store| store.take())),
+            skill_merges: Some(SKILL_MERGE_STORE.with(|store| store.take())),
+            endorsement_id: Some(ENDORSEMENT_ID_STORE.with(|id| id.get())),
+            endorsements: Some(ENDORSEMENT_STORE.with(|store| store.take())),
+            talent: Some(TALENT_STORE.with(|store| store.take())),
+            contact_request_id: Some(CONTACT_REQUEST_ID_STORE.with(|id| id.get())),
+            contact_requests: Some(CONTACT_REQUEST_STORE.with(|store| store.take())),
+            saved_search_id: Some(SAVED_SEARCH_ID_STORE.with(|id| id.get())),
+            saved_searches: Some(SAVED_SEARCH_STORE.with(|store| store.take())),
+            pending_alerts: Some(PENDING_ALERT_STORE.with(|store| store.take())),
+            notification_id: Some(NOTIFICATION_ID_STORE.with(|id| id.get())),
+            inbox: Some(INBOX_STORE.with(|store| store.take())),
+            webhook_id: Some(WEBHOOK_ID_STORE.with(|id| id.get())),
+            webhooks: Some(WEBHOOK_STORE.with(|store| store.take())),
+            delivery_id: Some(DELIVERY_ID_STORE.with(|id| id.get())),
+            deliveries: Some(DELIVERY_STORE.with(|store| store.take())),
+        }
+    }
+
+    pub fn copy() -> Self {
+        Self {
+            applicant_profiles: APPLICANT_PROFILE_STORE.with(|store| store.borrow().clone()),
+            company_profiles: COMPANY_PROFILE_STORE.with(|store| store.borrow().clone()),
+            job_id: JOB_ID_STORE.with(|id| id.get()),
+            jobs: JOB_STORE.with(|store| store.borrow().clone()),
+            application_id: APPLICATION_ID_STORE.with(|id| id.get()),
+            applications: APPLICATION_STORE.with(|store| store.borrow().clone()),
+            skill_id: SKILL_ID_STORE.with(|id| id.get()),
+            skills: SKILL_STORE.with(|store| store.borrow().clone()),
+            roles: Some(ROLE_STORE.with(|store| store.borrow().clone())),
+            admin_log_id: Some(ADMIN_LOG_ID_STORE.with(|id| id.get())),
+            admin_log: Some(ADMIN_LOG_STORE.with(|store| store.borrow().clone())),
+            company_members: Some(COMPANY_MEMBER_STORE.with(|store| store.borrow().clone())),
+            company_invites: Some(COMPANY_INVITE_STORE.with(|store| store.borrow().clone())),
+            ledger: LEDGER_CONFIG.with(|ledger| ledger.borrow().clone()),
+            expired_application_status: EXPIRED_APPLICATION_STATUS
+                .with(|status| status.borrow().clone()),
+            closing_jobs: Some(CLOSING_JOB_STORE.with(|store| store.borrow().clone())),
+            escrows: Some(ESCROW_STORE.with(|store| store.borrow().clone())),
+            skill_aliases: Some(SKILL_ALIAS_STORE.with(|store| store.borrow().clone())),
+            skill_parents: Some(SKILL_PARENT_STORE.with(|store| store.borrow().clone())),
+            skill_merges: Some(SKILL_MERGE_STORE.with(|store| store.borrow().clone())),
+            endorsement_id: Some(ENDORSEMENT_ID_STORE.with(|id| id.get())),
+            endorsements: Some(ENDORSEMENT_STORE.with(|store| store.borrow().clone())),
+            talent: Some(TALENT_STORE.with(|store| store.borrow().clone())),
+            contact_request_id: Some(CONTACT_REQUEST_ID_STORE.with(|id| id.get())),
+            contact_requests: Some(CONTACT_REQUEST_STORE.with(|store| store.borrow().clone())),
+            saved_search_id: Some(SAVED_SEARCH_ID_STORE.with(|id| id.get())),
+            saved_searches: Some(SAVED_SEARCH_STORE.with(|store| store.borrow().clone())),
+            pending_alerts: Some(PENDING_ALERT_STORE.with(|store| store.borrow().clone())),
+            notification_id: Some(NOTIFICATION_ID_STORE.with(|id| id.get())),
+            inbox: Some(INBOX_STORE.with(|store| store.borrow().clone())),
+            webhook_id: Some(WEBHOOK_ID_STORE.with(|id| id.get())),
+            webhooks: Some(WEBHOOK_STORE.with(|store| store.borrow().clone())),
+            delivery_id: Some(DELIVERY_ID_STORE.with(|id| id.get())),
+            deliveries: Some(DELIVERY_STORE.with(|store| store.borrow().clone())),
+        }
+    }
+
+    pub fn restore(self) {
+        // states saved before roles existed get them from the profiles they hold
+        let role_store = self.roles.unwrap_or_else(|| {
+            let mut role_store = RoleStore::new();
+            for principal in self.company_profiles.keys() {
+                role_store
+                    .entry(*principal)
+                    .or_default()
+                    .insert(Role::Company);
+            }
+            for principal in self.applicant_profiles.keys() {
+                role_store
+                    .entry(*principal)
+                    .or_default()
+                    .insert(Role::Applicant);
+            }
+            role_store
+        });
+
+        // states saved before teams existed make every company its own single owner
+        let member_store = self.company_members.unwrap_or_else(|| {
+            self.company_profiles
+                .iter()
+                .map(|(company_id, profile)| {
+                    let owner = CompanyMember {
+                        principal: *company_id,
+                        role: MemberRole::Owner,
+                        joined_at: profile.created_at,
+                    };
+                    (*company_id, BTreeMap::from([(*company_id, owner)]))
+                })
+                .collect()
+        });
+
+        ROLE_STORE.with(|store| store.replace(role_store));
+        COMPANY_MEMBER_STORE.with(|store| store.replace(member_store));
+        COMPANY_INVITE_STORE.with(|store| store.replace(self.company_invites.unwrap_or_default()));
+        LEDGER_CONFIG.with(|ledger| ledger.replace(self.ledger));
+        EXPIRED_APPLICATION_STATUS.with(|status| status.replace(self.expired_application_status));
+        CLOSING_JOB_STORE.with(|store| store.replace(self.closing_jobs.unwrap_or_default()));
+        ESCROW_STORE.with(|store| store.replace(self.escrows.unwrap_or_default()));
+        SKILL_ALIAS_STORE.with(|store| store.replace(self.skill_aliases.unwrap_or_default()));
+        SKILL_PARENT_STORE.with(|store| store.replace(self.skill_parents.unwrap_or_default()));
+        SKILL_MERGE_STORE.with(|store| store.replace(self.skill_merges.unwrap_or_default()));
+        ENDORSEMENT_ID_STORE.with(|id| id.set(self.endorsement_id.unwrap_or_default()));
+        ENDORSEMENT_STORE.with(|store| store.replace(self.endorsements.unwrap_or_default()));
+        TALENT_STORE.with(|store| store.replace(self.talent.unwrap_or_default()));
+        CONTACT_REQUEST_ID_STORE.with(|id| id.set(self.contact_request_id.unwrap_or_default()));
+        CONTACT_REQUEST_STORE
+            .with(|store| store.replace(self.contact_requests.unwrap_or_default()));
+        SAVED_SEARCH_ID_STORE.with(|id| id.set(self.saved_search_id.unwrap_or_default()));
+        SAVED_SEARCH_STORE.with(|store| store.replace(self.saved_searches.unwrap_or_default()));
+        PENDING_ALERT_STORE.with(|store| store.replace(self.pending_alerts.unwrap_or_default()));
+        NOTIFICATION_ID_STORE.with(|id| id.set(self.notification_id.unwrap_or_default()));
+        INBOX_STORE.with(|store| store.replace(self.inbox.unwrap_or_default()));
+        WEBHOOK_ID_STORE.with(|id| id.set(self.webhook_id.unwrap_or_default()));
+        WEBHOOK_STORE.with(|store| store.replace(self.webhooks.unwrap_or_default()));
+        DELIVERY_ID_STORE.with(|id| id.set(self.delivery_id.unwrap_or_default()));
+        DELIVERY_STORE.with(|store| store.replace(self.deliveries.unwrap_or_default()));
+        ADMIN_LOG_ID_STORE.with(|id| id.set(self.admin_log_id.unwrap_or_default()));
+        ADMIN_LOG_STORE.with(|store| store.replace(self.admin_log.unwrap_or_default()));
+
+        APPLICANT_PROFILE_STORE.with(|store| store.replace(self.applicant_profiles));
+        COMPANY_PROFILE_STORE.with(|store| store.replace(self.company_profiles));
+        JOB_ID_STORE.with(|id| id.set(self.job_id));
+        JOB_STORE.with(|store| store.replace(self.jobs));
+        APPLICATION_ID_STORE.with(|id| id.set(self.application_id));
+        APPLICATION_STORE.with(|store| store.replace(self.applications));
+        SKILL_ID_STORE.with(|id| id.set(self.skill_id));
+        SKILL_STORE.with(|store| store.replace(self.skills));
+    }
+}
+
+fn encode(state: &StableState) -> StateSnapshot {
+    StateSnapshot {
+        version: STATE_VERSION,
+        data: Encode!(state).expect("failed to encode stable state"),
+    }
+}
+
+// EXPORT
+//  a whole state doesn't fit in one message (about 2 MiB in, 3 MiB out),
+//  so snapshots leave and come back in chunks of SNAPSHOT_CHUNK_SIZE.
+
+pub const SNAPSHOT_CHUNK_SIZE: usize = 1024 * 1024;
+
+thread_local! {
+    // the snapshot taken by start_export, so every chunk comes from the same state
+    static EXPORT_BUFFER: RefCell<Option<StateSnapshot>> = RefCell::default();
+    // the snapshot being uploaded for a dry run
+    static UPLOAD_BUFFER: RefCell<Option<StateSnapshot>> = RefCell::default();
+}
+
+pub fn start_export() -> StateExport {
+    let snapshot = encode(&StableState::copy());
+    let export = StateExport {
+        version: snapshot.version,
+        size: snapshot.data.len() as u64,
+        chunk_size: SNAPSHOT_CHUNK_SIZE as u64,
+    };
+
+    EXPORT_BUFFER.with(|buffer| buffer.replace(Some(snapshot)));
+
+    export
+}
+
+pub fn export_chunk(offset: u64) -> BoardResult<Vec<u8>> {
+    EXPORT_BUFFER.with(|buffer| {
+        let buffer = buffer.borrow();
+        let data = &buffer
+            .as_ref()
+            .ok_or_else(|| BoardError::conflict("No export was started"))?
+            .data;
+
+        let start = usize::try_from(offset)
+            .ok()
+            .filter(|start| *start <= data.len())
+            .ok_or_else(|| BoardError::validation("offset", "is past the end of the export"))?;
+        let end = data.len().min(start + SNAPSHOT_CHUNK_SIZE);
+
+        Ok(data[start..end].to_vec())
+    })
+}
+
+// drops whatever was uploaded before
+pub fn start_upload(version: u32) {
+    let snapshot = StateSnapshot {
+        version,
+        data: Vec::new(),
+    };
+
+    UPLOAD_BUFFER.with(|buffer| buffer.replace(Some(snapshot)));
+}
+
+// appends to the upload, returns how many bytes it holds now
+pub fn upload_chunk(chunk: Vec<u8>) -> BoardResult<u64> {
+    if chunk.len() > SNAPSHOT_CHUNK_SIZE {
+        return Err(BoardError::validation(
+            "data",
+            &format!("chunks are at most {SNAPSHOT_CHUNK_SIZE} bytes"),
+        ));
+    }
+
+    UPLOAD_BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        let snapshot = buffer
+            .as_mut()
+            .ok_or_else(|| BoardError::conflict("No upload was started"))?;
+
+        snapshot.data.extend(chunk);
+
+        Ok(snapshot.data.len() as u64)
+    })
+}
+
+pub fn uploaded() -> BoardResult<StateSnapshot> {
+    UPLOAD_BUFFER
+        .with(|buffer| buffer.borrow().clone())
+        .ok_or_else(|| BoardError::conflict("No upload was started"))
+}
+
+// layout of the upgrades region: [len: u64 le][candid encoded StateSnapshot]
+pub fn save() {
+    let snapshot = encode(&StableState::take());
+    let bytes = Encode!(&snapshot).expect("failed to encode state snapshot");
+
+    let mut memory = get_memory(UPGRADES_MEMORY_ID);
+    let mut writer = Writer::new(&mut memory, 0);
+    writer
+        .write(&(bytes.len() as u64).to_le_bytes())
+        .expect("failed to write stable state");
+    writer.write(&bytes).expect("failed to write stable state");
+}
+
+pub fn load() {
+    let memory = get_memory(UPGRADES_MEMORY_ID);
+
+    // nothing was saved yet, i.e. upgrading from a version without stable state
+    if memory.size() == 0 {
+        return;
+    }
+
+    let mut reader = Reader::new(&memory, 0);
+    let mut len = [0u8; 8];
+    reader.read(&mut len).expect("failed to read stable state");
+
+    let mut bytes = vec![0u8; u64::from_le_bytes(len) as usize];
+    reader
+        .read(&mut bytes)
+        .expect("failed to read stable state");
+
+    // states saved before versioning was introduced hold a bare StableState
+    let snapshot = Decode!(&bytes, StateSnapshot).unwrap_or(StateSnapshot {
+        version: 1,
+        data: bytes,
+    });
+
+    migration::migrate(snapshot)
+        .expect("failed to migrate stable state")
+        .restore();
+}
@@ -0,0 +1,353 @@
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::time;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::APPLICANT_PROFILE_STORE;
+use crate::{endorsements, index, skills};
+use crate::{ApplicantProfile, ApplicantProfileResponse, BoardError, BoardResult, Proficiency};
+
+pub const MAX_MESSAGE_LENGTH: usize = 1000;
+
+// applicants are hidden from talent search until they opt in
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct TalentSettings {
+    pub discoverable: bool,
+    // when the applicant can start, in nanoseconds
+    pub available_from: u64,
+    // only shared with companies whose contact request the applicant accepted
+    pub contact_email: String,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct TalentParams {
+    pub discoverable: bool,
+    pub available_from: u64,
+    pub contact_email: String,
+}
+
+// `proficiency` is the minimum level asked for
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct TalentSkill {
+    pub skill_id: u16,
+    pub proficiency: Proficiency,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct TalentQuery {
+    pub skills: Vec<TalentSkill>,
+    // applicants that can start by this time
+    pub available_by: Option<u64>,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct TalentHit {
+    pub profile: ApplicantProfileResponse,
+    pub available_from: u64,
+    // requested skills the applicant holds at the asked level, itself or one below it
+    pub matched_skills: Vec<u16>,
+    pub score: u64,
+    // endorsement weight of the matching skills, breaks ties between equal scores
+    pub weight: u64,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize, PartialEq)]
+pub enum ContactStatus {
+    #[default]
+    Pending,
+    Accepted,
+    Declined,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ContactRequest {
+    pub id: u64,
+    pub company_id: Principal,
+    pub applicant_id: Principal,
+    pub requested_by: Principal,
+    pub message: String,
+    pub status: ContactStatus,
+    // filled in from the talent settings once the applicant accepts
+    pub contact_email: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+pub type TalentStore = BTreeMap<Principal, TalentSettings>;
+pub type ContactRequestStore = BTreeMap<u64, ContactRequest>;
+
+thread_local! {
+    pub(crate) static TALENT_STORE: RefCell<TalentStore> = RefCell::default();
+
+    pub(crate) static CONTACT_REQUEST_ID_STORE: Cell<u64> = const { Cell::new(0) };
+    pub(crate) static CONTACT_REQUEST_STORE: RefCell<ContactRequestStore> = RefCell::default();
+
+    // applicant -> request ids and company -> request ids, derived from CONTACT_REQUEST_STORE
+    static CONTACT_BY_APPLICANT_STORE: RefCell<BTreeMap<Principal, BTreeSet<u64>>> = RefCell::default();
+    static CONTACT_BY_COMPANY_STORE: RefCell<BTreeMap<Principal, BTreeSet<u64>>> = RefCell::default();
+}
+
+fn index_request(request: &ContactRequest) {
+    CONTACT_BY_APPLICANT_STORE.with(|store| {
+        store
+            .borrow_mut()
+            .entry(request.applicant_id)
+            .or_default()
+            .insert(request.id);
+    });
+    CONTACT_BY_COMPANY_STORE.with(|store| {
+        store
+            .borrow_mut()
+            .entry(request.company_id)
+            .or_default()
+            .insert(request.id);
+    });
+}
+
+pub fn rebuild() {
+    CONTACT_BY_APPLICANT_STORE.with(|store| store.borrow_mut().clear());
+    CONTACT_BY_COMPANY_STORE.with(|store| store.borrow_mut().clear());
+
+    CONTACT_REQUEST_STORE.with(|store| store.borrow().values().for_each(index_request));
+}
+
+pub fn settings(applicant_id: &Principal) -> Option<TalentSettings> {
+    TALENT_STORE.with(|store| store.borrow().get(applicant_id).cloned())
+}
+
+pub fn is_discoverable(applicant_id: &Principal) -> bool {
+    settings(applicant_id).is_some_and(|settings| settings.discoverable)
+}
+
+pub fn update(applicant_id: Principal, params: TalentParams) -> BoardResult<TalentSettings> {
+    let has_profile =
+        APPLICANT_PROFILE_STORE.with(|store| store.borrow().contains_key(&applicant_id));
+
+    if !has_profile {
+        return Err(BoardError::not_found("applicant profile", applicant_id));
+    }
+
+    if params.discoverable && params.contact_email.trim().is_empty() {
+        return Err(BoardError::validation(
+            "contact_email",
+            "is required to be discoverable",
+        ));
+    }
+
+    let settings = TalentSettings {
+        discoverable: params.discoverable,
+        available_from: params.available_from,
+        contact_email: params.contact_email.trim().to_lowercase(),
+        updated_at: time(),
+    };
+
+    TALENT_STORE.with(|store| store.borrow_mut().insert(applicant_id, settings.clone()));
+
+    Ok(settings)
+}
+
+// ranked by the number of requested skills matched, then by endorsement weight.
+// only opted in applicants are considered. returns the scores of the hits, `hit` builds them.
+pub fn search(query: &TalentQuery) -> BoardResult<Vec<((u64, u64), Principal)>> {
+    if query.skills.is_empty() {
+        return Err(BoardError::validation(
+            "skills",
+            "at least one skill is required",
+        ));
+    }
+
+    if query.skills.len() > skills::MAX_SKILLS_PER_REQUEST {
+        return Err(BoardError::validation("skills", "too many skills"));
+    }
+
+    let candidates: BTreeSet<Principal> = query
+        .skills
+        .iter()
+        .flat_map(|wanted| skills::subtree(wanted.skill_id))
+        .flat_map(|skill_id| index::applicants_by_skill(&skill_id))
+        .collect();
+
+    let ranked = APPLICANT_PROFILE_STORE.with(|store| {
+        let store = store.borrow();
+
+        candidates
+            .iter()
+            .filter_map(|applicant_id| {
+                let profile = store.get(applicant_id)?;
+                let (_, matched_skills, held_skills) = matches(query, applicant_id, profile)?;
+                let score = matched_skills.len() as u64;
+
+                Some((
+                    (score, endorsements::weight(applicant_id, &held_skills)),
+                    *applicant_id,
+                ))
+            })
+            .collect()
+    });
+
+    Ok(ranked)
+}
+
+pub fn hit(query: &TalentQuery, applicant_id: &Principal) -> Option<TalentHit> {
+    APPLICANT_PROFILE_STORE.with(|store| {
+        let store = store.borrow();
+        let profile = store.get(applicant_id)?;
+        let (available_from, matched_skills, held_skills) = matches(query, applicant_id, profile)?;
+
+        Some(TalentHit {
+            profile: profile.into(),
+            available_from,
+            score: matched_skills.len() as u64,
+            matched_skills,
+            weight: endorsements::weight(applicant_id, &held_skills),
+        })
+    })
+}
+
+// when a discoverable applicant can start, the requested skills they match and the held
+// skills matching them. None when the applicant doesn't show up in the search.
+fn matches(
+    query: &TalentQuery,
+    applicant_id: &Principal,
+    profile: &ApplicantProfile,
+) -> Option<(u64, Vec<u16>, BTreeSet<u16>)> {
+    let settings = settings(applicant_id).filter(|settings| settings.discoverable)?;
+
+    if query
+        .available_by
+        .is_some_and(|available_by| settings.available_from > available_by)
+    {
+        return None;
+    }
+
+    let mut matched_skills = Vec::new();
+    let mut held_skills = BTreeSet::new();
+
+    for wanted in &query.skills {
+        let held: Vec<u16> = profile
+            .skills
+            .iter()
+            .filter(|(id, held)| {
+                skills::covers(wanted.skill_id, **id) && held.proficiency >= wanted.proficiency
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        if !held.is_empty() {
+            matched_skills.push(wanted.skill_id);
+            held_skills.extend(held);
+        }
+    }
+
+    if matched_skills.is_empty() {
+        return None;
+    }
+
+    Some((settings.available_from, matched_skills, held_skills))
+}
+
+pub fn request_contact(
+    requested_by: Principal,
+    company_id: Principal,
+    applicant_id: Principal,
+    message: String,
+) -> BoardResult<ContactRequest> {
+    if !is_discoverable(&applicant_id) {
+        return Err(BoardError::not_found(
+            "discoverable applicant",
+            applicant_id,
+        ));
+    }
+
+    if message.chars().count() > MAX_MESSAGE_LENGTH {
+        return Err(BoardError::validation("message", "is too long"));
+    }
+
+    let pending = of_company(&company_id).iter().any(|request| {
+        request.applicant_id == applicant_id && request.status == ContactStatus::Pending
+    });
+
+    if pending {
+        return Err(BoardError::conflict(
+            "A contact request to this applicant is already pending",
+        ));
+    }
+
+    let id = CONTACT_REQUEST_ID_STORE.with(|id| {
+        id.set(id.get() + 1);
+        id.get()
+    });
+
+    let request = ContactRequest {
+        id,
+        company_id,
+        applicant_id,
+        requested_by,
+        message,
+        status: ContactStatus::Pending,
+        contact_email: None,
+        created_at: time(),
+        updated_at: time(),
+    };
+
+    CONTACT_REQUEST_STORE.with(|store| store.borrow_mut().insert(id, request.clone()));
+    index_request(&request);
+
+    Ok(request)
+}
+
+pub fn respond(applicant_id: Principal, id: u64, accept: bool) -> BoardResult<ContactRequest> {
+    let request = get(id)
+        .filter(|request| request.applicant_id == applicant_id)
+        .ok_or_else(|| BoardError::not_found("contact request", id))?;
+
+    if request.status != ContactStatus::Pending {
+        return Err(BoardError::conflict("Contact request was already answered"));
+    }
+
+    let request = if accept {
+        ContactRequest {
+            status: ContactStatus::Accepted,
+            contact_email: settings(&applicant_id).map(|settings| settings.contact_email),
+            updated_at: time(),
+            ..request
+        }
+    } else {
+        ContactRequest {
+            status: ContactStatus::Declined,
+            updated_at: time(),
+            ..request
+        }
+    };
+
+    CONTACT_REQUEST_STORE.with(|store| store.borrow_mut().insert(id, request.clone()));
+
+    Ok(request)
+}
+
+pub fn get(id: u64) -> Option<ContactRequest> {
+    CONTACT_REQUEST_STORE.with(|store| store.borrow().get(&id).cloned())
+}
+
+pub fn ids_of_applicant(applicant_id: &Principal) -> BTreeSet<u64> {
+    CONTACT_BY_APPLICANT_STORE
+        .with(|store| store.borrow().get(applicant_id).cloned())
+        .unwrap_or_default()
+}
+
+pub fn ids_of_company(company_id: &Principal) -> BTreeSet<u64> {
+    CONTACT_BY_COMPANY_STORE
+        .with(|store| store.borrow().get(company_id).cloned())
+        .unwrap_or_default()
+}
+
+fn of_company(company_id: &Principal) -> Vec<ContactRequest> {
+    CONTACT_REQUEST_STORE.with(|store| {
+        let store = store.borrow();
+        ids_of_company(company_id)
+            .iter()
+            .filter_map(|id| store.get(id).cloned())
+            .collect()
+    })
+}
@@ -0,0 +1,344 @@
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::time;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use crate::roles::{self, Role};
+use crate::{BoardError, BoardResult};
+
+// ordered by what they may do, so a check can ask for "at least Recruiter"
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MemberRole {
+    Viewer,
+    Recruiter,
+    Owner,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CompanyMember {
+    pub principal: Principal,
+    pub role: MemberRole,
+    pub joined_at: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CompanyInvite {
+    pub company_id: Principal,
+    pub principal: Principal,
+    pub role: MemberRole,
+    pub invited_by: Principal,
+    pub created_at: u64,
+}
+
+// a company is identified by the principal that created its profile
+pub type CompanyMemberStore = BTreeMap<Principal, BTreeMap<Principal, CompanyMember>>;
+// invitee -> company -> invite
+pub type CompanyInviteStore = BTreeMap<Principal, BTreeMap<Principal, CompanyInvite>>;
+
+thread_local! {
+    pub(crate) static COMPANY_MEMBER_STORE: RefCell<CompanyMemberStore> = RefCell::default();
+    pub(crate) static COMPANY_INVITE_STORE: RefCell<CompanyInviteStore> = RefCell::default();
+
+    // member -> company, derived from COMPANY_MEMBER_STORE
+    static MEMBER_COMPANY_STORE: RefCell<BTreeMap<Principal, Principal>> = RefCell::default();
+}
+
+pub fn rebuild() {
+    let members: BTreeMap<Principal, Principal> = COMPANY_MEMBER_STORE.with(|store| {
+        store
+            .borrow()
+            .iter()
+            .flat_map(|(company_id, members)| {
+                members
+                    .keys()
+                    .map(move |principal| (*principal, *company_id))
+            })
+            .collect()
+    });
+
+    MEMBER_COMPANY_STORE.with(|store| store.replace(members));
+}
+
+fn insert_member(company_id: Principal, principal: Principal, role: MemberRole) -> CompanyMember {
+    let member = CompanyMember {
+        principal,
+        role,
+        joined_at: time(),
+    };
+
+    COMPANY_MEMBER_STORE.with(|store| {
+        store
+            .borrow_mut()
+            .entry(company_id)
+            .or_default()
+            .insert(principal, member.clone());
+    });
+    MEMBER_COMPANY_STORE.with(|store| store.borrow_mut().insert(principal, company_id));
+    roles::grant(principal, Role::Company);
+
+    member
+}
+
+// called once when the company profile is created
+pub fn create(company_id: Principal) {
+    insert_member(company_id, company_id, MemberRole::Owner);
+}
+
+pub fn exists(company_id: &Principal) -> bool {
+    COMPANY_MEMBER_STORE.with(|store| store.borrow().contains_key(company_id))
+}
+
+pub fn company_of(principal: &Principal) -> Option<Principal> {
+    MEMBER_COMPANY_STORE.with(|store| store.borrow().get(principal).cloned())
+}
+
+pub fn members_of(company_id: &Principal) -> Vec<CompanyMember> {
+    COMPANY_MEMBER_STORE.with(|store| {
+        store
+            .borrow()
+            .get(company_id)
+            .map(|members| members.values().cloned().collect())
+            .unwrap_or_default()
+    })
+}
+
+fn role_in(company_id: &Principal, principal: &Principal) -> Option<MemberRole> {
+    COMPANY_MEMBER_STORE.with(|store| {
+        store
+            .borrow()
+            .get(company_id)
+            .and_then(|members| members.get(principal))
+            .map(|member| member.role)
+    })
+}
+
+// the company the caller acts for, as long as their role in it is at least `required`
+pub fn authorize(principal: &Principal, required: MemberRole) -> BoardResult<Principal> {
+    let company_id = company_of(principal)
+        .ok_or_else(|| BoardError::unauthorized("User is not a member of any Company"))?;
+
+    // an Admin revoking the Company role suspends the member without touching the team
+    if !roles::has_role(principal, Role::Company) {
+        return Err(BoardError::unauthorized("User is not a Company"));
+    }
+
+    match role_in(&company_id, principal) {
+        Some(role) if role >= required => Ok(company_id),
+        _ => Err(BoardError::unauthorized(
+            "Member role does not allow this action",
+        )),
+    }
+}
+
+// same as `authorize`, but for a specific company
+pub fn authorize_for(
+    company_id: &Principal,
+    principal: &Principal,
+    required: MemberRole,
+) -> BoardResult<()> {
+    if !roles::has_role(principal, Role::Company) {
+        return Err(BoardError::unauthorized("User is not a Company"));
+    }
+
+    match role_in(company_id, principal) {
+        Some(role) if role >= required => Ok(()),
+        Some(_) => Err(BoardError::unauthorized(
+            "Member role does not allow this action",
+        )),
+        None => Err(BoardError::unauthorized("Job belongs to another company")),
+    }
+}
+
+pub fn invite(
+    company_id: Principal,
+    invited_by: Principal,
+    principal: Principal,
+    role: MemberRole,
+) -> BoardResult<CompanyInvite> {
+    if company_of(&principal).is_some() {
+        return Err(BoardError::conflict("User already belongs to a Company"));
+    }
+
+    let invite = CompanyInvite {
+        company_id,
+        principal,
+        role,
+        invited_by,
+        created_at: time(),
+    };
+
+    COMPANY_INVITE_STORE.with(|store| {
+        store
+            .borrow_mut()
+            .entry(principal)
+            .or_default()
+            .insert(company_id, invite.clone());
+    });
+
+    Ok(invite)
+}
+
+pub fn invites_of(principal: &Principal) -> Vec<CompanyInvite> {
+    COMPANY_INVITE_STORE.with(|store| {
+        store
+            .borrow()
+            .get(principal)
+            .map(|invites| invites.values().cloned().collect())
+            .unwrap_or_default()
+    })
+}
+
+pub fn accept(principal: Principal, company_id: Principal) -> BoardResult<CompanyMember> {
+    if company_of(&principal).is_some() {
+        return Err(BoardError::conflict("User already belongs to a Company"));
+    }
+
+    if roles::has_role(&principal, Role::Applicant) {
+        return Err(BoardError::conflict("User already exist as Applicant"));
+    }
+
+    let invite = COMPANY_INVITE_STORE
+        .with(|store| {
+            store
+                .borrow_mut()
+                .get_mut(&principal)
+                .and_then(|invites| invites.remove(&company_id))
+        })
+        .ok_or_else(|| BoardError::not_found("invite", company_id))?;
+
+    // the rest of the invites are void once the user joined a company
+    COMPANY_INVITE_STORE.with(|store| store.borrow_mut().remove(&principal));
+
+    Ok(insert_member(company_id, principal, invite.role))
+}
+
+pub fn remove(company_id: Principal, principal: Principal) -> BoardResult<()> {
+    // the company is keyed by its founder, who stays an Owner for as long as it exists
+    if principal == company_id {
+        return Err(BoardError::conflict(
+            "The founder of a Company can't be removed",
+        ));
+    }
+
+    let role = role_in(&company_id, &principal)
+        .ok_or_else(|| BoardError::not_found("member", principal))?;
+
+    let owners = members_of(&company_id)
+        .iter()
+        .filter(|member| member.role == MemberRole::Owner)
+        .count();
+
+    if role == MemberRole::Owner && owners == 1 {
+        return Err(BoardError::conflict("A Company needs at least one Owner"));
+    }
+
+    COMPANY_MEMBER_STORE.with(|store| {
+        if let Some(members) = store.borrow_mut().get_mut(&company_id) {
+            members.remove(&principal);
+        }
+    });
+    MEMBER_COMPANY_STORE.with(|store| store.borrow_mut().remove(&principal));
+    roles::revoke(principal, Role::Company);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(byte: u8) -> Principal {
+        Principal::from_slice(&[byte])
+    }
+
+    // company 1, founded by principal 1
+    fn team(members: &[(u8, MemberRole)]) -> Principal {
+        let company_id = principal(1);
+
+        COMPANY_MEMBER_STORE.with(|store| {
+            let mut store = store.borrow_mut();
+            let team = store.entry(company_id).or_default();
+
+            for (byte, role) in members {
+                team.insert(
+                    principal(*byte),
+                    CompanyMember {
+                        principal: principal(*byte),
+                        role: *role,
+                        joined_at: 0,
+                    },
+                );
+                roles::grant(principal(*byte), Role::Company);
+            }
+        });
+        rebuild();
+
+        company_id
+    }
+
+    #[test]
+    fn a_higher_role_may_do_what_a_lower_one_may() {
+        let company_id = team(&[(1, MemberRole::Owner), (2, MemberRole::Recruiter)]);
+
+        assert_eq!(
+            authorize(&principal(1), MemberRole::Recruiter),
+            Ok(company_id)
+        );
+        assert_eq!(authorize(&principal(2), MemberRole::Viewer), Ok(company_id));
+        assert!(authorize(&principal(2), MemberRole::Owner).is_err());
+        assert!(authorize(&principal(3), MemberRole::Viewer).is_err());
+    }
+
+    #[test]
+    fn members_act_for_their_own_company_only() {
+        let company_id = team(&[(1, MemberRole::Owner)]);
+
+        assert!(authorize_for(&company_id, &principal(1), MemberRole::Owner).is_ok());
+        assert!(authorize_for(&principal(9), &principal(1), MemberRole::Viewer).is_err());
+    }
+
+    #[test]
+    fn the_last_owner_stays() {
+        let company_id = team(&[(1, MemberRole::Owner), (2, MemberRole::Viewer)]);
+
+        assert!(remove(company_id, principal(1)).is_err());
+        assert_eq!(company_of(&principal(1)), Some(company_id));
+    }
+
+    #[test]
+    fn a_removed_member_loses_the_company_role() {
+        let company_id = team(&[(1, MemberRole::Owner), (2, MemberRole::Viewer)]);
+
+        assert_eq!(remove(company_id, principal(2)), Ok(()));
+        assert_eq!(company_of(&principal(2)), None);
+        assert!(!roles::has_role(&principal(2), Role::Company));
+        assert_eq!(members_of(&company_id).len(), 1);
+    }
+
+    #[test]
+    fn joining_needs_an_invite() {
+        let company_id = team(&[(1, MemberRole::Owner)]);
+
+        assert!(accept(principal(2), company_id).is_err());
+        assert!(accept(principal(1), company_id).is_err());
+    }
+
+    #[test]
+    fn the_founder_cannot_be_removed() {
+        let company_id = team(&[(1, MemberRole::Owner), (2, MemberRole::Owner)]);
+
+        assert!(remove(company_id, principal(1)).is_err());
+        assert_eq!(remove(company_id, principal(2)), Ok(()));
+        assert!(exists(&company_id));
+    }
+
+    #[test]
+    fn a_member_without_the_company_role_is_suspended() {
+        let company_id = team(&[(1, MemberRole::Owner)]);
+        roles::revoke(principal(1), Role::Company);
+
+        assert!(authorize(&principal(1), MemberRole::Viewer).is_err());
+        assert!(authorize_for(&company_id, &principal(1), MemberRole::Viewer).is_err());
+        assert_eq!(company_of(&principal(1)), Some(company_id));
+    }
+}
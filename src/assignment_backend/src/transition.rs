@@ -0,0 +1,410 @@
+use ic_cdk::api::time;
+
+use crate::inbox::{self, NotificationKind};
+use crate::{escrow, expiry, index, webhooks};
+use crate::{Application, ApplicationStatus, BoardError, BoardResult, Job, JobStatus};
+use crate::{APPLICATION_STORE, JOB_STORE};
+
+// who is allowed to trigger an edge, the endpoints still check that the caller
+// actually is the applicant / company of the record before claiming to be one.
+// Admin stands for admins and moderators, who may take any edge in the table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Actor {
+    Applicant,
+    Company,
+    System,
+    Admin,
+}
+
+use Actor::*;
+use ApplicationStatus::*;
+use JobStatus::*;
+
+const APPLICATION_TRANSITIONS: &[(ApplicationStatus, ApplicationStatus, Actor)] = &[
+    (Applied, Offer, Company),
+    (Applied, Rejected, Company),
+    (Applied, Withdraw, Applicant),
+    (Offer, Accepted, Applicant),
+    (Offer, Rejected, Applicant),
+    (Offer, Withdraw, Applicant),
+    // pending applications are closed out when their job stops taking hires,
+    // an expired job can withdraw them instead, see expiry::application_status
+    (Applied, Rejected, System),
+    (Applied, Withdraw, System),
+    (Offer, Rejected, System),
+];
+
+const JOB_TRANSITIONS: &[(JobStatus, JobStatus, Actor)] = &[
+    (Open, Closed, Company),
+    (Open, Canceled, Company),
+    // a job is filled once an offer is accepted, or closes when it expires
+    (Open, Closed, System),
+    // only expired jobs, see job()
+    (Closed, Open, Company),
+];
+
+fn is_allowed<S: PartialEq>(table: &[(S, S, Actor)], from: &S, to: &S, actor: Actor) -> bool {
+    table.iter().any(|(edge_from, edge_to, edge_actor)| {
+        edge_from == from && edge_to == to && (*edge_actor == actor || actor == Admin)
+    })
+}
+
+pub fn application(
+    application: Application,
+    status: ApplicationStatus,
+    actor: Actor,
+) -> BoardResult<Application> {
+    if !is_allowed(APPLICATION_TRANSITIONS, &application.status, &status, actor) {
+        return Err(BoardError::invalid_transition(application.status, status));
+    }
+
+    // accepting closes the job, which has to be possible before anything is written:
+    // an Err reply doesn't undo the writes made before it
+    if status == Accepted {
+        let job_record = JOB_STORE
+            .with(|store| store.borrow().get(&application.job_id).cloned())
+            .ok_or_else(|| BoardError::not_found("job", application.job_id))?;
+
+        if !is_allowed(JOB_TRANSITIONS, &job_record.status, &Closed, System) {
+            return Err(BoardError::conflict("Job is no longer open"));
+        }
+    }
+
+    let updated = Application {
+        status,
+        updated_at: time(),
+        ..application.clone()
+    };
+
+    index::insert_application(updated.clone());
+
+    on_application_transition(&application, &updated, actor);
+
+    Ok(updated)
+}
+
+pub fn job(job: Job, status: JobStatus, actor: Actor) -> BoardResult<Job> {
+    let mut budget = usize::MAX;
+    job_within(job, status, actor, &mut budget)
+}
+
+// like `job`, closing out `budget` of the pending applications at most.
+// the budget is left at 0 when some may still be pending, `close_out` takes those.
+pub fn job_within(
+    job: Job,
+    status: JobStatus,
+    actor: Actor,
+    budget: &mut usize,
+) -> BoardResult<Job> {
+    // reopening escrows the bounty again, which only the reopen_job endpoint does
+    let reopens_expired = job.expired_at.is_some() && actor == Company;
+
+    if !is_allowed(JOB_TRANSITIONS, &job.status, &status, actor)
+        || (status == Open && !reopens_expired)
+    {
+        return Err(BoardError::invalid_transition(job.status, status));
+    }
+
+    let updated = Job {
+        expired_at: job.expired_at.filter(|_| status != Open),
+        status,
+        updated_at: time(),
+        ..job.clone()
+    };
+
+    // the pending applications are closed out with the job. like accepting, every edge is
+    // checked before the job is written: an Err reply doesn't undo the writes made before it
+    let closing = closing_out(&updated, *budget);
+    if let Some((value, status)) = closing
+        .iter()
+        .find(|(value, status)| !is_allowed(APPLICATION_TRANSITIONS, &value.status, status, System))
+    {
+        return Err(BoardError::invalid_transition(&value.status, status));
+    }
+    *budget -= closing.len();
+
+    index::insert_job(updated.clone());
+
+    on_job_transition(&job, &updated, actor, closing);
+
+    Ok(updated)
+}
+
+// closes out `budget` more of the applications still pending on an ended job.
+// true once none are left.
+pub fn close_out(job: &Job, budget: &mut usize) -> bool {
+    let limit = *budget;
+    let closing = closing_out(job, limit);
+    *budget -= closing.len();
+
+    let done = closing.len() < limit;
+    close(job, closing);
+
+    done
+}
+
+// the applications still waiting on a job that stopped taking hires, `limit` at most,
+// with the status each one ends in
+fn closing_out(job: &Job, limit: usize) -> Vec<(Application, ApplicationStatus)> {
+    if job.status == Open {
+        return Vec::new();
+    }
+
+    APPLICATION_STORE.with(|store| {
+        let store = store.borrow();
+
+        index::applications_by_job(&job.id)
+            .iter()
+            .filter_map(|id| store.get(id))
+            .filter(|value| value.status == Applied || value.status == Offer)
+            .map(|value| {
+                let status = if value.status == Applied && job.expired_at.is_some() {
+                    expiry::application_status()
+                } else {
+                    Rejected
+                };
+
+                (value.clone(), status)
+            })
+            .take(limit)
+            .collect()
+    })
+}
+
+// HOOKS
+//  side effects of a transition, run after the record itself was stored.
+//  a transition checks everything that can fail before it stores anything, so the hooks
+//  don't return errors. should one still fail, they trap, which undoes the whole call.
+
+fn on_application_transition(before: &Application, after: &Application, actor: Actor) {
+    notify_application(before, after, actor);
+
+    if after.status != Accepted {
+        return;
+    }
+
+    // the bounty goes out before the job closes, closing would refund it otherwise
+    if let Some(to) = after.referrer.or(after.applicant_id) {
+        escrow::release(after.job_id, to);
+    }
+
+    // application() checked the job can be closed. if that still fails, trapping
+    // throws away the accepted application and the released escrow with it.
+    let closed = JOB_STORE
+        .with(|store| store.borrow().get(&after.job_id).cloned())
+        .ok_or_else(|| BoardError::not_found("job", after.job_id))
+        .and_then(|job_record| job(job_record, Closed, System));
+
+    if closed.is_err() {
+        ic_cdk::trap("failed to close the job of an accepted application");
+    }
+}
+
+fn on_job_transition(
+    _before: &Job,
+    after: &Job,
+    _actor: Actor,
+    closing: Vec<(Application, ApplicationStatus)>,
+) {
+    if after.status == Open {
+        return;
+    }
+
+    // a job that ends without a hire gives its bounty back
+    escrow::refund(after.id);
+
+    close(after, closing);
+}
+
+// applicants still waiting hear about the job before their applications are closed out
+fn close(job: &Job, closing: Vec<(Application, ApplicationStatus)>) {
+    let kind = match job.status {
+        Canceled => NotificationKind::JobCanceled { job_id: job.id },
+        _ => NotificationKind::JobClosed { job_id: job.id },
+    };
+    for (value, _) in closing.iter() {
+        if let Some(applicant_id) = value.applicant_id {
+            inbox::push(applicant_id, kind.clone());
+        }
+    }
+
+    // job_within checked these edges before the job was stored,
+    // a failure here would leave it half closed out
+    for (value, status) in closing {
+        if application(value, status, System).is_err() {
+            ic_cdk::trap("failed to close out an application of an ended job");
+        }
+    }
+}
+
+// tells the other side of an application what changed. changes the system makes
+// when a job ends are left out, the job notification already covers them.
+fn notify_application(before: &Application, after: &Application, actor: Actor) {
+    let application_id = after.id;
+    let job_id = after.job_id;
+
+    let (to_applicant, kind) = match (&before.status, &after.status, actor) {
+        (_, Offer, _) => (
+            true,
+            NotificationKind::OfferMade {
+                application_id,
+                job_id,
+            },
+        ),
+        (_, Accepted, _) => (
+            false,
+            NotificationKind::OfferAccepted {
+                application_id,
+                job_id,
+            },
+        ),
+        (Offer, Rejected, Applicant) => (
+            false,
+            NotificationKind::OfferDeclined {
+                application_id,
+                job_id,
+            },
+        ),
+        (_, Rejected, Company | Admin) => (
+            true,
+            NotificationKind::ApplicationRejected {
+                application_id,
+                job_id,
+            },
+        ),
+        (_, Withdraw, Applicant | Admin) => (
+            false,
+            NotificationKind::ApplicationWithdrawn {
+                application_id,
+                job_id,
+            },
+        ),
+        _ => return,
+    };
+
+    let company_id = JOB_STORE
+        .with(|store| store.borrow().get(&job_id).cloned())
+        .and_then(|job| job.company_id);
+
+    // the company's webhooks hear about both sides
+    if let Some(company_id) = company_id {
+        webhooks::enqueue(company_id, &kind);
+    }
+
+    if to_applicant {
+        if let Some(applicant_id) = after.applicant_id {
+            inbox::push(applicant_id, kind);
+        }
+    } else if let Some(company_id) = company_id {
+        inbox::push_to_company(&company_id, kind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn application_allowed(from: ApplicationStatus, to: ApplicationStatus, actor: Actor) -> bool {
+        is_allowed(APPLICATION_TRANSITIONS, &from, &to, actor)
+    }
+
+    fn job_allowed(from: JobStatus, to: JobStatus, actor: Actor) -> bool {
+        is_allowed(JOB_TRANSITIONS, &from, &to, actor)
+    }
+
+    #[test]
+    fn company_answers_applications() {
+        assert!(application_allowed(Applied, Offer, Company));
+        assert!(application_allowed(Applied, Rejected, Company));
+        assert!(!application_allowed(Applied, Offer, Applicant));
+        assert!(!application_allowed(Offer, Accepted, Company));
+    }
+
+    #[test]
+    fn applicant_answers_offers() {
+        assert!(application_allowed(Offer, Accepted, Applicant));
+        assert!(application_allowed(Offer, Rejected, Applicant));
+        assert!(application_allowed(Offer, Withdraw, Applicant));
+        assert!(application_allowed(Applied, Withdraw, Applicant));
+        assert!(!application_allowed(Applied, Accepted, Applicant));
+    }
+
+    #[test]
+    fn system_only_closes_out_pending_applications() {
+        assert!(application_allowed(Applied, Rejected, System));
+        assert!(application_allowed(Applied, Withdraw, System));
+        assert!(application_allowed(Offer, Rejected, System));
+        assert!(!application_allowed(Offer, Accepted, System));
+        assert!(!application_allowed(Applied, Offer, System));
+    }
+
+    #[test]
+    fn admin_takes_any_edge_in_the_table_only() {
+        assert!(application_allowed(Applied, Offer, Admin));
+        assert!(application_allowed(Offer, Accepted, Admin));
+        assert!(!application_allowed(Accepted, Applied, Admin));
+        assert!(!application_allowed(Rejected, Offer, Admin));
+    }
+
+    #[test]
+    fn job_edges_per_actor() {
+        assert!(job_allowed(Open, Closed, Company));
+        assert!(job_allowed(Open, Canceled, Company));
+        assert!(job_allowed(Open, Closed, System));
+        assert!(job_allowed(Closed, Open, Company));
+        assert!(!job_allowed(Open, Canceled, System));
+        assert!(!job_allowed(Closed, Open, System));
+        assert!(!job_allowed(Canceled, Open, Company));
+        assert!(!job_allowed(Canceled, Closed, Admin));
+    }
+
+    #[test]
+    fn an_ended_job_closes_out_only_its_pending_applications() {
+        let pending = [
+            (1, 1, Applied),
+            (2, 1, Offer),
+            (3, 1, Withdraw),
+            (4, 2, Applied),
+        ];
+        APPLICATION_STORE.with(|store| {
+            let mut store = store.borrow_mut();
+
+            for (id, job_id, status) in pending {
+                let value = Application {
+                    id,
+                    job_id,
+                    status,
+                    ..Default::default()
+                };
+                store.insert(id, value);
+            }
+        });
+        index::rebuild();
+
+        let ended = |status, expired_at| Job {
+            id: 1,
+            status,
+            expired_at,
+            ..Default::default()
+        };
+        let statuses = |job: &Job| -> Vec<(u64, ApplicationStatus)> {
+            closing_out(job, usize::MAX)
+                .into_iter()
+                .map(|(value, status)| (value.id, status))
+                .collect()
+        };
+
+        assert!(statuses(&ended(Open, None)).is_empty());
+        assert_eq!(
+            statuses(&ended(Canceled, None)),
+            vec![(1, Rejected), (2, Rejected)]
+        );
+
+        expiry::configure(Withdraw);
+        assert_eq!(
+            statuses(&ended(Closed, Some(1))),
+            vec![(1, Withdraw), (2, Rejected)]
+        );
+        assert_eq!(closing_out(&ended(Closed, Some(1)), 1).len(), 1);
+    }
+}
@@ -0,0 +1,662 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use hmac::{Hmac, Mac};
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse, TransformArgs,
+    TransformContext,
+};
+use ic_cdk::api::time;
+use ic_cdk_timers::TimerId;
+use serde::Serialize;
+use sha2::Sha256;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use crate::inbox::NotificationKind;
+use crate::{BoardError, BoardResult};
+
+pub const MAX_WEBHOOKS: usize = 5;
+pub const MAX_URL_LENGTH: usize = 2048;
+pub const MIN_SECRET_LENGTH: usize = 16;
+pub const MAX_SECRET_LENGTH: usize = 256;
+
+// a delivery is given up on after this many attempts, the first retry waits a minute
+// and every further one twice as long as the one before
+pub const MAX_ATTEMPTS: u32 = 8;
+const FIRST_RETRY_AFTER: u64 = 60 * 1_000_000_000;
+
+// deliveries sent per timer tick, the rest wait for the next one
+const BATCH_SIZE: usize = 10;
+
+// older failed deliveries are dropped once a company has this many
+pub const MAX_FAILED_DELIVERIES: usize = 100;
+
+// the receiver's answer is dropped by the transform, but still has to fit in here
+const MAX_RESPONSE_BYTES: u64 = 8 * 1024;
+// enough for an outcall on a 13 node subnet, the part that isn't used is refunded
+const OUTCALL_CYCLES: u128 = 1_000_000_000;
+
+pub const TRANSFORM_METHOD: &str = "transform_webhook_response";
+
+// a company's endpoint, the secret signs every payload sent to it
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct Webhook {
+    pub id: u64,
+    pub company_id: Principal,
+    pub url: String,
+    pub secret: String,
+    pub created_by: Principal,
+    pub created_at: u64,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct WebhookParams {
+    pub url: String,
+    pub secret: String,
+}
+
+// the secret is never handed back out
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct WebhookResponse {
+    pub id: u64,
+    pub url: String,
+    pub created_by: Principal,
+    pub created_at: u64,
+}
+
+impl From<&Webhook> for WebhookResponse {
+    fn from(webhook: &Webhook) -> Self {
+        Self {
+            id: webhook.id,
+            url: webhook.url.clone(),
+            created_by: webhook.created_by,
+            created_at: webhook.created_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub enum DeliveryStatus {
+    Pending,
+    // MAX_ATTEMPTS ran out, only sent again on request
+    Failed,
+}
+
+// one payload for one webhook. delivered payloads are dropped from the store.
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct Delivery {
+    pub id: u64,
+    pub webhook_id: u64,
+    pub company_id: Principal,
+    pub event: String,
+    pub body: String,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    delivery_id: u64,
+    event: &'a str,
+    company_id: String,
+    job_id: u64,
+    application_id: u64,
+    created_at: u64,
+}
+
+pub type WebhookStore = BTreeMap<u64, Webhook>;
+pub type DeliveryStore = BTreeMap<u64, Delivery>;
+
+thread_local! {
+    pub(crate) static WEBHOOK_ID_STORE: Cell<u64> = const { Cell::new(0) };
+    pub(crate) static WEBHOOK_STORE: RefCell<WebhookStore> = RefCell::default();
+
+    pub(crate) static DELIVERY_ID_STORE: Cell<u64> = const { Cell::new(0) };
+    pub(crate) static DELIVERY_STORE: RefCell<DeliveryStore> = RefCell::default();
+
+    // timers don't survive an upgrade, post_upgrade arms the next one again
+    static TIMER: Cell<Option<TimerId>> = const { Cell::new(None) };
+    // deliveries with an outcall in flight, so a timer tick doesn't send them twice
+    static SENDING: RefCell<BTreeSet<u64>> = RefCell::default();
+}
+
+pub fn of_company(company_id: &Principal) -> Vec<Webhook> {
+    WEBHOOK_STORE.with(|store| {
+        store
+            .borrow()
+            .values()
+            .filter(|webhook| webhook.company_id == *company_id)
+            .cloned()
+            .collect()
+    })
+}
+
+// local replicas can reach a stub server over plain http, anything else needs https
+fn validate_url(url: &str) -> BoardResult<()> {
+    let secure = url
+        .strip_prefix("https://")
+        .is_some_and(|rest| !authority(rest).is_empty());
+    let local = url
+        .strip_prefix("http://")
+        .is_some_and(|rest| is_local(authority(rest)));
+
+    if !secure && !local {
+        return Err(BoardError::validation("url", "must be an https url"));
+    }
+
+    if url.len() > MAX_URL_LENGTH {
+        return Err(BoardError::validation("url", "is too long"));
+    }
+
+    Ok(())
+}
+
+// the host and port, everything before the path, query or fragment
+fn authority(rest: &str) -> &str {
+    rest.split(['/', '?', '#']).next().unwrap_or_default()
+}
+
+// the host has to be exactly localhost or 127.0.0.1, so localhost.example.com doesn't pass
+fn is_local(authority: &str) -> bool {
+    let (host, port) = match authority.split_once(':') {
+        Some((host, port)) => (host, Some(port)),
+        None => (authority, None),
+    };
+
+    ["localhost", "127.0.0.1"].contains(&host)
+        && port.is_none_or(|port| !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()))
+}
+
+pub fn register(
+    company_id: Principal,
+    created_by: Principal,
+    params: WebhookParams,
+) -> BoardResult<Webhook> {
+    let url = params.url.trim().to_string();
+    validate_url(&url)?;
+
+    if params.secret.len() < MIN_SECRET_LENGTH || params.secret.len() > MAX_SECRET_LENGTH {
+        return Err(BoardError::validation(
+            "secret",
+            &format!("must be between {MIN_SECRET_LENGTH} and {MAX_SECRET_LENGTH} bytes"),
+        ));
+    }
+
+    let webhooks = of_company(&company_id);
+
+    if webhooks.len() >= MAX_WEBHOOKS {
+        return Err(BoardError::conflict(&format!(
+            "Webhook limit of {MAX_WEBHOOKS} reached"
+        )));
+    }
+
+    if webhooks.iter().any(|webhook| webhook.url == url) {
+        return Err(BoardError::conflict("Webhook is already registered"));
+    }
+
+    let id = WEBHOOK_ID_STORE.with(|id| {
+        id.set(id.get() + 1);
+        id.get()
+    });
+
+    let webhook = Webhook {
+        id,
+        company_id,
+        url,
+        secret: params.secret,
+        created_by,
+        created_at: time(),
+    };
+
+    WEBHOOK_STORE.with(|store| store.borrow_mut().insert(id, webhook.clone()));
+
+    Ok(webhook)
+}
+
+// drops the webhook along with the payloads still waiting for it
+pub fn remove(company_id: Principal, id: u64) -> BoardResult<()> {
+    WEBHOOK_STORE
+        .with(|store| {
+            let mut store = store.borrow_mut();
+            store
+                .get(&id)
+                .filter(|webhook| webhook.company_id == company_id)?;
+            store.remove(&id)
+        })
+        .ok_or_else(|| BoardError::not_found("webhook", id))?;
+
+    DELIVERY_STORE.with(|store| {
+        store
+            .borrow_mut()
+            .retain(|_, delivery| delivery.webhook_id != id)
+    });
+
+    Ok(())
+}
+
+pub fn deliveries_of(company_id: &Principal) -> BTreeSet<u64> {
+    DELIVERY_STORE.with(|store| {
+        store
+            .borrow()
+            .values()
+            .filter(|delivery| delivery.company_id == *company_id)
+            .map(|delivery| delivery.id)
+            .collect()
+    })
+}
+
+pub fn get_delivery(id: u64) -> Option<Delivery> {
+    DELIVERY_STORE.with(|store| store.borrow().get(&id).cloned())
+}
+
+fn insert_delivery(delivery: Delivery) {
+    DELIVERY_STORE.with(|store| store.borrow_mut().insert(delivery.id, delivery));
+}
+
+// puts a failed delivery back in the queue with a fresh set of attempts
+pub fn retry(company_id: Principal, id: u64) -> BoardResult<Delivery> {
+    let delivery = get_delivery(id)
+        .filter(|delivery| delivery.company_id == company_id)
+        .ok_or_else(|| BoardError::not_found("delivery", id))?;
+
+    if delivery.status != DeliveryStatus::Failed {
+        return Err(BoardError::conflict("Delivery is still being retried"));
+    }
+
+    let delivery = Delivery {
+        status: DeliveryStatus::Pending,
+        attempts: 0,
+        next_attempt_at: time(),
+        updated_at: time(),
+        ..delivery
+    };
+
+    insert_delivery(delivery.clone());
+    schedule();
+
+    Ok(delivery)
+}
+
+// the application events an ATS cares about, the rest stay in the inbox
+fn event_of(kind: &NotificationKind) -> Option<(&'static str, u64, u64)> {
+    match kind {
+        NotificationKind::ApplicationReceived {
+            application_id,
+            job_id,
+        } => Some(("application_received", *application_id, *job_id)),
+        NotificationKind::ApplicationWithdrawn {
+            application_id,
+            job_id,
+        } => Some(("application_withdrawn", *application_id, *job_id)),
+        NotificationKind::OfferMade {
+            application_id,
+            job_id,
+        } => Some(("offer_made", *application_id, *job_id)),
+        NotificationKind::OfferAccepted {
+            application_id,
+            job_id,
+        } => Some(("offer_accepted", *application_id, *job_id)),
+        NotificationKind::OfferDeclined {
+            application_id,
+            job_id,
+        } => Some(("offer_declined", *application_id, *job_id)),
+        _ => None,
+    }
+}
+
+// queues one payload per webhook of the company, they go out on the next timer tick
+pub fn enqueue(company_id: Principal, kind: &NotificationKind) {
+    let Some((event, application_id, job_id)) = event_of(kind) else {
+        return;
+    };
+
+    for webhook in of_company(&company_id) {
+        let id = DELIVERY_ID_STORE.with(|id| {
+            id.set(id.get() + 1);
+            id.get()
+        });
+
+        let payload = Payload {
+            delivery_id: id,
+            event,
+            company_id: company_id.to_text(),
+            job_id,
+            application_id,
+            created_at: time(),
+        };
+
+        insert_delivery(Delivery {
+            id,
+            webhook_id: webhook.id,
+            company_id,
+            event: event.to_string(),
+            body: serde_json::to_string(&payload).expect("failed to encode webhook payload"),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: time(),
+            last_error: None,
+            created_at: time(),
+            updated_at: time(),
+        });
+    }
+
+    schedule();
+}
+
+// arms a timer for the earliest pending delivery, replacing the one armed before
+pub fn schedule() {
+    if let Some(timer) = TIMER.with(|timer| timer.take()) {
+        ic_cdk_timers::clear_timer(timer);
+    }
+
+    let sending = SENDING.with(|sending| sending.borrow().clone());
+    let Some(next_attempt_at) = DELIVERY_STORE.with(|store| {
+        store
+            .borrow()
+            .values()
+            .filter(|delivery| {
+                delivery.status == DeliveryStatus::Pending && !sending.contains(&delivery.id)
+            })
+            .map(|delivery| delivery.next_attempt_at)
+            .min()
+    }) else {
+        return;
+    };
+
+    let delay = Duration::from_nanos(next_attempt_at.saturating_sub(time()));
+    let timer = ic_cdk_timers::set_timer(delay, || {
+        TIMER.with(|timer| timer.set(None));
+        flush();
+    });
+
+    TIMER.with(|cell| cell.set(Some(timer)));
+}
+
+fn flush() {
+    let now = time();
+    let due: Vec<u64> = SENDING.with(|sending| {
+        let mut sending = sending.borrow_mut();
+        let due: Vec<u64> = DELIVERY_STORE.with(|store| {
+            store
+                .borrow()
+                .values()
+                .filter(|delivery| {
+                    delivery.status == DeliveryStatus::Pending
+                        && delivery.next_attempt_at <= now
+                        && !sending.contains(&delivery.id)
+                })
+                .take(BATCH_SIZE)
+                .map(|delivery| delivery.id)
+                .collect()
+        });
+
+        sending.extend(&due);
+        due
+    });
+
+    for id in due {
+        ic_cdk::spawn(deliver(id));
+    }
+
+    schedule();
+}
+
+async fn deliver(id: u64) {
+    let webhook = get_delivery(id).and_then(|delivery| {
+        WEBHOOK_STORE
+            .with(|store| store.borrow().get(&delivery.webhook_id).cloned())
+            .map(|webhook| (delivery, webhook))
+    });
+
+    if let Some((delivery, webhook)) = webhook {
+        let result = send(&delivery, &webhook).await;
+
+        // the webhook may have been removed while the outcall was in flight
+        if let Some(delivery) = get_delivery(id) {
+            finish(delivery, result);
+        }
+    }
+
+    SENDING.with(|sending| sending.borrow_mut().remove(&id));
+    schedule();
+}
+
+fn finish(delivery: Delivery, result: Result<(), String>) {
+    let Err(error) = result else {
+        DELIVERY_STORE.with(|store| store.borrow_mut().remove(&delivery.id));
+        return;
+    };
+
+    let attempts = delivery.attempts + 1;
+    let status = if attempts >= MAX_ATTEMPTS {
+        DeliveryStatus::Failed
+    } else {
+        DeliveryStatus::Pending
+    };
+
+    let company_id = delivery.company_id;
+    insert_delivery(Delivery {
+        status: status.clone(),
+        attempts,
+        next_attempt_at: time() + retry_delay(attempts),
+        last_error: Some(error),
+        updated_at: time(),
+        ..delivery
+    });
+
+    if status == DeliveryStatus::Failed {
+        prune_failed(&company_id);
+    }
+}
+
+fn prune_failed(company_id: &Principal) {
+    DELIVERY_STORE.with(|store| {
+        let mut store = store.borrow_mut();
+        let failed: Vec<u64> = store
+            .values()
+            .filter(|delivery| {
+                delivery.company_id == *company_id && delivery.status == DeliveryStatus::Failed
+            })
+            .map(|delivery| delivery.id)
+            .collect();
+
+        // ids go up with time, so the oldest come first
+        for id in failed
+            .iter()
+            .take(failed.len().saturating_sub(MAX_FAILED_DELIVERIES))
+        {
+            store.remove(id);
+        }
+    });
+}
+
+// the wait doubles with every failed attempt, up to 2^16 times the first one
+fn retry_delay(attempts: u32) -> u64 {
+    FIRST_RETRY_AFTER * (1 << attempts.saturating_sub(1).min(16))
+}
+
+// hex encoded HMAC-SHA256 of the body, keyed with the webhook's secret
+fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body.as_bytes());
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn is_success(status: &Nat) -> bool {
+    matches!(u64::try_from(&status.0), Ok(200..=299))
+}
+
+async fn send(delivery: &Delivery, webhook: &Webhook) -> Result<(), String> {
+    let header = |name: &str, value: String| HttpHeader {
+        name: name.to_string(),
+        value,
+    };
+
+    let request = CanisterHttpRequestArgument {
+        url: webhook.url.clone(),
+        max_response_bytes: Some(MAX_RESPONSE_BYTES),
+        method: HttpMethod::POST,
+        headers: vec![
+            header("Content-Type", "application/json".to_string()),
+            header("X-Webhook-Event", delivery.event.clone()),
+            header("X-Webhook-Delivery", delivery.id.to_string()),
+            header(
+                "X-Webhook-Signature",
+                format!("sha256={}", sign(&webhook.secret, &delivery.body)),
+            ),
+        ],
+        body: Some(delivery.body.clone().into_bytes()),
+        transform: Some(TransformContext::from_name(
+            TRANSFORM_METHOD.to_string(),
+            vec![],
+        )),
+    };
+
+    match http_request(request, OUTCALL_CYCLES).await {
+        Ok((response,)) if is_success(&response.status) => Ok(()),
+        Ok((response,)) => Err(format!("endpoint answered with status {}", response.status)),
+        Err((code, reason)) => Err(format!("{code:?}: {reason}")),
+    }
+}
+
+// replicas have to agree on the response, so only its status is kept
+pub fn transform(args: TransformArgs) -> HttpResponse {
+    HttpResponse {
+        status: args.response.status,
+        headers: Vec::new(),
+        body: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delivery(id: u64) -> Delivery {
+        Delivery {
+            id,
+            webhook_id: 1,
+            company_id: Principal::from_slice(&[1]),
+            event: "application.created".to_string(),
+            body: "{}".to_string(),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: 0,
+            last_error: None,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn signatures_are_hmac_sha256_in_hex() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_ne!(sign("another secret", "{}"), sign("the secret", "{}"));
+    }
+
+    #[test]
+    fn retries_back_off_exponentially_up_to_a_cap() {
+        assert_eq!(retry_delay(1), FIRST_RETRY_AFTER);
+        assert_eq!(retry_delay(2), 2 * FIRST_RETRY_AFTER);
+        assert_eq!(retry_delay(4), 8 * FIRST_RETRY_AFTER);
+        assert_eq!(retry_delay(17), retry_delay(40));
+    }
+
+    #[test]
+    fn endpoints_need_https_except_on_this_machine() {
+        assert!(validate_url("https://example.com/hook").is_ok());
+        assert!(validate_url("http://localhost:8080/hook").is_ok());
+        assert!(validate_url("http://127.0.0.1/hook").is_ok());
+        assert!(validate_url("http://example.com/hook").is_err());
+        assert!(validate_url("ftp://example.com/hook").is_err());
+        assert!(validate_url("https://").is_err());
+
+        let long = format!("https://example.com/{}", "a".repeat(MAX_URL_LENGTH));
+        assert!(validate_url(&long).is_err());
+    }
+
+    #[test]
+    fn plain_http_needs_exactly_a_local_host() {
+        assert!(validate_url("http://localhost").is_ok());
+        assert!(validate_url("http://127.0.0.1:4943?id=1").is_ok());
+        assert!(validate_url("http://localhost.evil.com/hook").is_err());
+        assert!(validate_url("http://127.0.0.1.evil.com/hook").is_err());
+        assert!(validate_url("http://localhost@evil.com/hook").is_err());
+        assert!(validate_url("http://localhost:80.evil.com/hook").is_err());
+        assert!(validate_url("http://localhost:/hook").is_err());
+    }
+
+    #[test]
+    fn only_2xx_answers_count_as_delivered() {
+        assert!(is_success(&Nat::from(200u64)));
+        assert!(is_success(&Nat::from(204u64)));
+        assert!(!is_success(&Nat::from(301u64)));
+        assert!(!is_success(&Nat::from(500u64)));
+    }
+
+    #[test]
+    fn a_delivered_payload_is_dropped() {
+        insert_delivery(delivery(1));
+
+        finish(delivery(1), Ok(()));
+
+        assert!(get_delivery(1).is_none());
+    }
+
+    #[test]
+    fn the_transform_keeps_only_the_status() {
+        let response = HttpResponse {
+            status: Nat::from(200u64),
+            headers: vec![HttpHeader {
+                name: "Date".to_string(),
+                value: "now".to_string(),
+            }],
+            body: b"ok".to_vec(),
+        };
+        let args = TransformArgs {
+            response,
+            context: Vec::new(),
+        };
+
+        let response = transform(args);
+
+        assert_eq!(response.status, Nat::from(200u64));
+        assert!(response.headers.is_empty());
+        assert!(response.body.is_empty());
+    }
+
+    #[test]
+    fn only_the_newest_failed_deliveries_are_kept() {
+        let total = MAX_FAILED_DELIVERIES as u64 + 5;
+        for id in 1..=total {
+            insert_delivery(Delivery {
+                status: DeliveryStatus::Failed,
+                ..delivery(id)
+            });
+        }
+        // pending deliveries and other companies don't count
+        insert_delivery(delivery(total + 1));
+        insert_delivery(Delivery {
+            company_id: Principal::from_slice(&[2]),
+            status: DeliveryStatus::Failed,
+            ..delivery(total + 2)
+        });
+
+        prune_failed(&Principal::from_slice(&[1]));
+
+        assert!(get_delivery(5).is_none());
+        assert!(get_delivery(6).is_some());
+        assert!(get_delivery(total + 1).is_some());
+        assert!(get_delivery(total + 2).is_some());
+    }
+}